@@ -0,0 +1,151 @@
+// ─────────────────────────────────────────────────────────────────
+//  agent_auth.rs — HMAC verification for enrolled student agents
+//
+//  Every request to /api/agent/* (and agent WebSocket upgrade) carries:
+//    X-Agent-Hostname   — the enrolled machine name
+//    X-Agent-Timestamp  — unix seconds when the request was signed
+//    X-Agent-Signature  — hex HMAC-SHA256(secret, "{timestamp}.{body}")
+// ─────────────────────────────────────────────────────────────────
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::redis_store;
+use crate::state::AppState;
+
+pub const HOSTNAME_HEADER: &str = "x-agent-hostname";
+pub const TIMESTAMP_HEADER: &str = "x-agent-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-agent-signature";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The enrolled machine behind a verified agent request or WebSocket
+#[derive(Debug, Clone)]
+pub struct AgentIdentity {
+    pub hostname: String,
+}
+
+fn mac_for(secret: &str, timestamp: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Check the signature headers against `body` and return the verified hostname.
+pub async fn verify_signed(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<String, StatusCode> {
+    let hostname = header_str(headers, HOSTNAME_HEADER).ok_or(StatusCode::UNAUTHORIZED)?;
+    let timestamp = header_str(headers, TIMESTAMP_HEADER).ok_or(StatusCode::UNAUTHORIZED)?;
+    let signature = header_str(headers, SIGNATURE_HEADER).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    // Reject stale or future-dated requests
+    let window = state.config.agent_signature_window_secs;
+    let signed_at: i64 = timestamp.parse().map_err(|_| StatusCode::UNAUTHORIZED)?;
+    if (chrono::Utc::now().timestamp() - signed_at).unsigned_abs() > window {
+        tracing::warn!("Agent {hostname}: signature timestamp outside window");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let enrollment = redis_store::get_enrollment(&mut conn, prefix, hostname)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or_else(|| {
+            tracing::warn!("Agent {hostname}: not enrolled");
+            StatusCode::UNAUTHORIZED
+        })?;
+
+    let expected = hex::decode(signature).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    if mac_for(&enrollment.secret, timestamp, body)
+        .verify_slice(&expected)
+        .is_err()
+    {
+        tracing::warn!("Agent {hostname}: bad signature");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // Each signature may only be used once within the window
+    let fresh = redis_store::claim_agent_nonce(&mut conn, prefix, hostname, signature, window * 2)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !fresh {
+        tracing::warn!("Agent {hostname}: replayed request rejected");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(hostname.to_string())
+}
+
+/// Middleware for /api/agent/*: enforce the per-endpoint body cap (413),
+/// verify the agent signature (401), apply the per-hostname rate limit (429)
+/// and make sure the `hostname` in the JSON body matches the signing machine.
+/// The verified machine is exposed as an [`AgentIdentity`] extension.
+pub async fn require_agent(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let endpoint = req.uri().path().rsplit('/').next().unwrap_or_default();
+    let limit = state.config.agent_body_limit_for(endpoint);
+
+    let (mut parts, body) = req.into_parts();
+    let Ok(bytes) = to_bytes(body, limit).await else {
+        // Refused before the signature can be checked, so counted against
+        // the client address; the hostname header is only informative
+        let claimed_host = header_str(&parts.headers, HOSTNAME_HEADER).unwrap_or("unknown");
+        let peer = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|c| c.0.ip());
+        tracing::warn!(
+            "Body over {limit} bytes on {} from {peer:?} (claims {claimed_host})",
+            parts.uri.path()
+        );
+        if let Some(peer) = peer {
+            state.agent_limiter.record_oversized(peer, claimed_host);
+        }
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+
+    let hostname = match verify_signed(&state, &parts.headers, &bytes).await {
+        Ok(hostname) => hostname,
+        Err(status) => return status.into_response(),
+    };
+
+    if !state.agent_limiter.try_acquire(&hostname) {
+        tracing::warn!("Agent {hostname}: rate limited on {}", parts.uri.path());
+        let retry_after = state.agent_limiter.retry_after_secs().to_string();
+        return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after)]).into_response();
+    }
+
+    if !bytes.is_empty() {
+        let claimed = serde_json::from_slice::<serde_json::Value>(&bytes)
+            .ok()
+            .and_then(|v| v["hostname"].as_str().map(str::to_string));
+        if let Some(claimed) = claimed {
+            if claimed != hostname {
+                tracing::warn!("Agent {hostname} sent data claiming to be {claimed}");
+                return StatusCode::FORBIDDEN.into_response();
+            }
+        }
+    }
+
+    parts.extensions.insert(AgentIdentity { hostname });
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
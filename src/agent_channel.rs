@@ -0,0 +1,160 @@
+// ─────────────────────────────────────────────────────────────────
+//  agent_channel.rs — Commands over the agent's own WebSocket
+//
+//  Agents keep an outbound connection to /ws/agent (upgrade signed
+//  like /api/agent/*), so commands reach them through firewalls,
+//  NAT and VLANs and whatever their registered IP says.
+//
+//  Server → agent, one frame per command, with the same signed
+//  headers an HTTP command would carry (see server_key.rs):
+//    { "type": "command", "id": "...", "method": "POST",
+//      "path": "/lock/soft", "body": "<signed JSON text>", "headers": {...} }
+//  Agent → server, answering it:
+//    { "type": "result", "id": "...", "status": 200, "body": {...} }
+// ─────────────────────────────────────────────────────────────────
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::Extension;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use crate::agent_auth::AgentIdentity;
+use crate::commands;
+use crate::server_key;
+use crate::state::AppState;
+
+/// How long to wait for the agent to answer a command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// GET /ws/agent — the agent's command channel
+pub async fn ws_agent(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    Extension(agent): Extension<AgentIdentity>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_channel(socket, state, agent.hostname))
+}
+
+async fn handle_channel(socket: WebSocket, state: Arc<AppState>, hostname: String) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Message>();
+
+    // Forward channel → WebSocket, until a close is sent from our side
+    let mut send_task = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let closing = matches!(msg, Message::Close(_));
+            if ws_tx.send(msg).await.is_err() || closing {
+                break;
+            }
+        }
+    });
+
+    state.agent_channels.insert(hostname.clone(), tx.clone());
+    tracing::info!("📡 Command channel open: {hostname}");
+    tokio::spawn(commands::flush(state.clone(), hostname.clone()));
+
+    loop {
+        let msg = tokio::select! {
+            msg = ws_rx.next() => msg,
+            // Closed from our side, e.g. the agent was revoked
+            _ = &mut send_task => break,
+        };
+        let Some(Ok(msg)) = msg else { break };
+        let text = match msg {
+            Message::Text(t) => t,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let Ok(parsed) = serde_json::from_str::<Value>(&text) else { continue };
+
+        if parsed["type"] == "result" {
+            let id = parsed["id"].as_str().unwrap_or_default();
+            // Only the machine a command went to may answer it
+            let waiter = state.pending_replies.remove_if(id, |_, (target, _)| target == &hostname);
+            if let Some((_, (_, reply))) = waiter {
+                let _ = reply.send(parsed);
+            }
+        }
+    }
+
+    // Cleanup — only if a newer connection hasn't taken over; commands
+    // still waiting on this one fail now rather than at the timeout
+    if state.agent_channels.remove_if(&hostname, |_, t| t.same_channel(&tx)).is_some() {
+        state.pending_replies.retain(|_, (target, _)| target != &hostname);
+    }
+    tracing::info!("📡 Command channel closed: {hostname}");
+    send_task.abort();
+}
+
+/// A signed command frame for `hostname`, as sent over the channel and
+/// handed out in heartbeat responses.
+pub fn frame(state: &AppState, id: &str, hostname: &str, method: &str, path: &str, body: Option<&Value>) -> Value {
+    let bytes = body.map(|b| b.to_string().into_bytes()).unwrap_or_default();
+    let headers: serde_json::Map<String, Value> =
+        server_key::command_headers(&state.signing_key, method, path, hostname, &bytes)
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Value::String(value)))
+            .collect();
+
+    json!({
+        "type": "command",
+        "id": id,
+        "method": method,
+        "path": path,
+        "body": String::from_utf8_lossy(&bytes),
+        "headers": headers,
+    })
+}
+
+/// The wait for a reply; its slot goes when the waiting stops, including
+/// when the caller gives up on it.
+struct ReplySlot<'a> {
+    state: &'a AppState,
+    id: &'a str,
+}
+
+impl Drop for ReplySlot<'_> {
+    fn drop(&mut self) {
+        self.state.pending_replies.remove(self.id);
+    }
+}
+
+/// Send command `id` over `hostname`'s channel and wait for the answer:
+/// `(status, body)`, or why it could not be delivered.
+/// `None` if the agent has no channel open.
+pub async fn send(
+    state: &AppState,
+    id: &str,
+    hostname: &str,
+    method: &str,
+    path: &str,
+    body: Option<&Value>,
+) -> Option<Result<(u16, Value), String>> {
+    let tx = state.agent_channels.get(hostname)?.clone();
+
+    let (reply_tx, reply_rx) = oneshot::channel();
+    state.pending_replies.insert(id.to_string(), (hostname.to_string(), reply_tx));
+    let _slot = ReplySlot { state, id };
+
+    let frame = frame(state, id, hostname, method, path, body);
+    if tx.send(Message::Text(frame.to_string())).is_err() {
+        return Some(Err("Command channel closed".to_string()));
+    }
+
+    let outcome = match tokio::time::timeout(REPLY_TIMEOUT, reply_rx).await {
+        // A reply without a usable status proves nothing ran, so it counts
+        // as undelivered and the command is retried
+        Ok(Ok(reply)) => match reply["status"].as_u64().and_then(|s| u16::try_from(s).ok()) {
+            Some(status) => Ok((status, reply["body"].clone())),
+            None => Err("Reply over command channel has no status".to_string()),
+        },
+        Ok(Err(_)) => Err("Command channel closed".to_string()),
+        Err(_) => Err("No reply over command channel".to_string()),
+    };
+    Some(outcome)
+}
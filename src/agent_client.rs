@@ -0,0 +1,143 @@
+// ─────────────────────────────────────────────────────────────────
+//  agent_client.rs — Outgoing requests from the backend to student
+//  agents, signed with the server key (see server_key.rs)
+//
+//  Commands go over the agent's command channel when it has one
+//  open (see agent_channel.rs) and to http://{ip}:{port} otherwise.
+// ─────────────────────────────────────────────────────────────────
+
+use redis::aio::ConnectionManager;
+use reqwest::{Client, RequestBuilder};
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::agent_channel;
+use crate::redis_store;
+use crate::server_key;
+use crate::state::AppState;
+
+/// Where an agent can be reached, from the "hostname|ip|port" registry entry
+#[derive(Debug, Clone)]
+pub struct AgentAddr {
+    pub hostname: String,
+    pub ip: String,
+    pub port: String,
+}
+
+impl AgentAddr {
+    pub fn parse(entry: &str) -> Option<Self> {
+        let parts: Vec<&str> = entry.split('|').collect();
+        if parts.len() < 3 {
+            return None;
+        }
+        Some(Self {
+            hostname: parts[0].to_string(),
+            ip: parts[1].to_string(),
+            port: parts[2].to_string(),
+        })
+    }
+}
+
+/// Look up one agent in the registry.
+pub async fn find_agent(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    hostname: &str,
+) -> redis::RedisResult<Option<AgentAddr>> {
+    let agents = redis_store::get_all_agents(conn, prefix).await?;
+    Ok(agents
+        .iter()
+        .filter(|e| e.starts_with(&format!("{hostname}|")))
+        .find_map(|e| AgentAddr::parse(e)))
+}
+
+pub fn http_client() -> reqwest::Result<Client> {
+    Client::builder().timeout(Duration::from_secs(5)).build()
+}
+
+/// Build a signed request to `path` on the agent, with an optional JSON body.
+pub fn signed_request(
+    state: &AppState,
+    client: &Client,
+    method: reqwest::Method,
+    agent: &AgentAddr,
+    path: &str,
+    body: Option<&Value>,
+) -> RequestBuilder {
+    let url = format!("http://{}:{}{path}", agent.ip, agent.port);
+    let bytes = body.map(|b| b.to_string().into_bytes()).unwrap_or_default();
+
+    let mut req = client.request(method.clone(), &url);
+    for (name, value) in
+        server_key::command_headers(&state.signing_key, method.as_str(), path, &agent.hostname, &bytes)
+    {
+        req = req.header(name, value);
+    }
+    if body.is_some() {
+        req = req
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(bytes);
+    }
+    req
+}
+
+/// How an agent answered a command
+pub enum Reply {
+    /// Accepted, with the agent's response body
+    Ok(Value),
+    /// Reached but refused, with its HTTP status
+    Status(u16),
+    /// Could not be reached
+    Unreachable(String),
+}
+
+/// Send a signed command to the agent, over its channel if it has one.
+pub async fn send_command(
+    state: &AppState,
+    method: reqwest::Method,
+    agent: &AgentAddr,
+    path: &str,
+    body: Option<&Value>,
+) -> Reply {
+    let id = crate::auth::new_token()[..16].to_string();
+    send_command_as(state, &id, method, &agent.hostname, Some(agent), path, body).await
+}
+
+/// Send command `id` to `hostname`: over its channel if it has one, else
+/// over HTTP to `agent` when its address is known.
+pub async fn send_command_as(
+    state: &AppState,
+    id: &str,
+    method: reqwest::Method,
+    hostname: &str,
+    agent: Option<&AgentAddr>,
+    path: &str,
+    body: Option<&Value>,
+) -> Reply {
+    if let Some(outcome) = agent_channel::send(state, id, hostname, method.as_str(), path, body).await {
+        tracing::debug!("{path} to {hostname} over command channel");
+        return match outcome {
+            Ok((status, body)) if (200..300).contains(&status) => {
+                Reply::Ok(if body.is_null() { serde_json::json!({ "status": "ok" }) } else { body })
+            }
+            Ok((status, _)) => Reply::Status(status),
+            Err(e) => Reply::Unreachable(e),
+        };
+    }
+
+    let Some(agent) = agent else {
+        return Reply::Unreachable("Not registered".to_string());
+    };
+    let client = match http_client() {
+        Ok(client) => client,
+        Err(e) => return Reply::Unreachable(e.to_string()),
+    };
+    let req = signed_request(state, &client, method, agent, path, body).header(server_key::COMMAND_ID_HEADER, id);
+    match req.send().await {
+        Ok(resp) if resp.status().is_success() => {
+            Reply::Ok(resp.json().await.unwrap_or(serde_json::json!({ "status": "ok" })))
+        }
+        Ok(resp) => Reply::Status(resp.status().as_u16()),
+        Err(e) => Reply::Unreachable(e.to_string()),
+    }
+}
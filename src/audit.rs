@@ -0,0 +1,60 @@
+// ─────────────────────────────────────────────────────────────────
+//  audit.rs — Persistent record of teacher actions
+//
+//  Entries live in the `{prefix}:audit` sorted set, scored by
+//  timestamp in milliseconds, for `audit_retention_days`; see
+//  GET /api/audit.
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::Utc;
+use serde_json::Value;
+
+use crate::auth::Identity;
+use crate::models::{AuditEntry, AuditResult};
+use crate::redis_store;
+use crate::state::AppState;
+
+/// Record one teacher action. Failures are logged, never surfaced to the caller.
+pub async fn record(
+    state: &AppState,
+    actor: &Identity,
+    action: &str,
+    targets: Vec<String>,
+    params: Value,
+    results: Vec<AuditResult>,
+) {
+    record_as(state, &actor.username, action, targets, params, results).await;
+}
+
+/// Record an action the server took on a teacher's behalf, such as
+/// releasing a timed lock.
+pub async fn record_as(
+    state: &AppState,
+    actor: &str,
+    action: &str,
+    targets: Vec<String>,
+    params: Value,
+    results: Vec<AuditResult>,
+) {
+    let entry = AuditEntry {
+        actor: actor.to_string(),
+        action: action.to_string(),
+        targets,
+        params,
+        results,
+        timestamp: Utc::now(),
+    };
+
+    let retention = chrono::Duration::try_days(state.config.audit_retention_days)
+        .unwrap_or(chrono::Duration::MAX);
+    let retain_from = entry
+        .timestamp
+        .checked_sub_signed(retention)
+        .map_or(i64::MIN, |t| t.timestamp_millis());
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    if let Err(e) = redis_store::add_audit_entry(&mut conn, prefix, &entry, retain_from).await {
+        tracing::warn!("Failed to write audit entry for {} {}: {e}", entry.actor, entry.action);
+    }
+}
@@ -0,0 +1,240 @@
+// ─────────────────────────────────────────────────────────────────
+//  auth.rs — Teacher accounts, login sessions and the middleware
+//  that enforces the permission table (see permissions.rs)
+// ─────────────────────────────────────────────────────────────────
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::Utc;
+use rand::RngCore;
+use std::sync::{Arc, OnceLock};
+
+use crate::agent_auth::{self, AgentIdentity};
+use crate::models::{Role, TeacherAccount};
+use crate::permissions::{self, Access};
+use crate::redis_store;
+use crate::state::AppState;
+
+/// Name of the cookie carrying the session token for the dashboard
+pub const SESSION_COOKIE: &str = "nishack_session";
+
+/// The authenticated teacher behind a request, inserted as a request extension
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub username: String,
+    pub display_name: String,
+    pub role: Role,
+}
+
+pub fn hash_password(password: &str) -> Option<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .ok()
+        .map(|h| h.to_string())
+}
+
+/// Hash checked against when the username does not exist, so a failed
+/// login takes as long whether or not the account is real
+fn dummy_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| hash_password(&new_token()).unwrap_or_default())
+}
+
+/// Check a login; unknown accounts still cost one argon2 verification.
+pub fn verify_login(password: &str, account: Option<&TeacherAccount>) -> bool {
+    match account {
+        Some(a) => verify_password(password, &a.password_hash),
+        None => {
+            verify_password(password, dummy_hash());
+            false
+        }
+    }
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Random 256-bit token, hex encoded
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Pull the session token from `Authorization: Bearer ...` or the session cookie
+pub fn session_token(headers: &HeaderMap) -> Option<String> {
+    if let Some(value) = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        if let Some(token) = value.strip_prefix("Bearer ") {
+            return Some(token.trim().to_string());
+        }
+    }
+
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Session token for a request. Browsers cannot set headers on WebSocket
+/// upgrades, so those may also pass it as `?token=...`.
+fn request_token(req: &Request) -> Option<String> {
+    if let Some(token) = session_token(req.headers()) {
+        return Some(token);
+    }
+    if !req.headers().contains_key(header::UPGRADE) {
+        return None;
+    }
+    req.uri()
+        .query()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == "token")
+        .map(|(_, value)| value.to_string())
+}
+
+/// Resolve a session token to the teacher it belongs to.
+pub async fn identity_for_token(state: &AppState, token: &str) -> Option<Identity> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let username = redis_store::get_auth_session(&mut conn, prefix, token)
+        .await
+        .ok()??;
+    // The account may have been deleted since the session was issued
+    let account = redis_store::get_teacher(&mut conn, prefix, &username)
+        .await
+        .ok()??;
+
+    Some(Identity {
+        username: account.username,
+        display_name: account.display_name,
+        role: account.role,
+    })
+}
+
+/// Middleware: enforce the permission table for the matched route. Teacher
+/// routes get the caller's [`Identity`] as a request extension.
+pub async fn authorize(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+
+    match permissions::required_access(req.method(), &route) {
+        // Agent routes carry their own signature check
+        Some(Access::Public) | Some(Access::Agent) => Ok(next.run(req).await),
+        Some(Access::Role(min_role)) => {
+            let token = request_token(&req).ok_or(StatusCode::UNAUTHORIZED)?;
+            let identity = authenticate(&state, &token, min_role, &route).await?;
+            req.extensions_mut().insert(identity);
+            Ok(next.run(req).await)
+        }
+        Some(Access::RoleOrAgent(min_role)) => {
+            if let Some(token) = request_token(&req) {
+                let identity = authenticate(&state, &token, min_role, &route).await?;
+                req.extensions_mut().insert(identity);
+            } else {
+                // Agents sign the upgrade request with an empty body
+                let hostname = agent_auth::verify_signed(&state, req.headers(), &[]).await?;
+                req.extensions_mut().insert(AgentIdentity { hostname });
+            }
+            Ok(next.run(req).await)
+        }
+        None => {
+            tracing::error!("No permission entry for {} {route}", req.method());
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+/// Resolve the caller's session and check it holds at least `min_role`.
+async fn authenticate(
+    state: &AppState,
+    token: &str,
+    min_role: Role,
+    route: &str,
+) -> Result<Identity, StatusCode> {
+    let identity = identity_for_token(state, token)
+        .await
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    if identity.role < min_role {
+        tracing::warn!("{} ({:?}) denied {route}", identity.username, identity.role);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(identity)
+}
+
+/// Make sure an admin account exists: create "admin" when there are no
+/// accounts at all, or promote it when no account holds the admin role.
+pub async fn ensure_bootstrap_admin(state: &AppState) {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let existing = match redis_store::get_all_teachers(&mut conn, prefix).await {
+        Ok(existing) => existing,
+        Err(e) => {
+            tracing::warn!("Could not read teacher accounts: {e}");
+            return;
+        }
+    };
+
+    if existing.iter().any(|a| a.role == Role::Admin) {
+        return;
+    }
+    if let Some(mut admin) = existing.into_iter().find(|a| a.username == "admin") {
+        admin.role = Role::Admin;
+        match redis_store::store_teacher(&mut conn, prefix, &admin).await {
+            Ok(()) => tracing::info!("🔑 Promoted teacher account 'admin' to the admin role"),
+            Err(e) => tracing::warn!("Could not promote 'admin' account: {e}"),
+        }
+        return;
+    }
+
+    let generated = state.config.admin_password.is_empty();
+    let password = if generated {
+        new_token()[..16].to_string()
+    } else {
+        state.config.admin_password.clone()
+    };
+
+    let Some(password_hash) = hash_password(&password) else {
+        tracing::warn!("Could not hash bootstrap admin password");
+        return;
+    };
+    let account = TeacherAccount {
+        username: "admin".to_string(),
+        display_name: "Administrator".to_string(),
+        password_hash,
+        role: Role::Admin,
+        created_at: Utc::now(),
+    };
+
+    match redis_store::store_teacher(&mut conn, prefix, &account).await {
+        Ok(()) if generated => tracing::warn!(
+            "🔑 Created teacher account 'admin' with generated password: {password} — change it after logging in"
+        ),
+        Ok(()) => tracing::info!("🔑 Created teacher account 'admin' from config"),
+        Err(e) => tracing::warn!("Could not create bootstrap admin account: {e}"),
+    }
+}
@@ -0,0 +1,434 @@
+// ─────────────────────────────────────────────────────────────────
+//  commands.rs — Durable queue for commands to student agents
+//
+//  Every lock / open-url is stored in {prefix}:commands and queued in
+//  {prefix}:command_queue:{hostname} before it is sent. If the agent
+//  can't be reached (asleep, rebooting) the command stays pending:
+//    • retried with exponential backoff every few seconds,
+//    • sent as soon as the agent opens its command channel,
+//    • handed out in the response to the agent's next heartbeat,
+//      after which the agent acks it via /api/agent/command-ack.
+//  Statuses: pending → sending | delivered → acked | failed, or expired
+//  when `command_ttl_secs` passes first. A command is claimed (sending,
+//  or delivered for heartbeats) before it goes out, so only one of these
+//  paths sends it; a claim that outlives `SEND_LEASE_SECS` is taken over
+//  by the retry task. Writes only go through if the stored status is
+//  still the one the writer saw, so a finished command stays finished.
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::agent_channel;
+use crate::agent_client::{self, AgentAddr, Reply};
+use crate::auth;
+use crate::models::AgentCommand;
+use crate::redis_store;
+use crate::state::AppState;
+
+const BACKOFF_BASE_SECS: i64 = 5;
+const BACKOFF_MAX_SECS: i64 = 120;
+
+/// How long a claimed command may be in flight before it is tried again
+const SEND_LEASE_SECS: i64 = 30;
+
+/// Finished commands stay inspectable this long
+const RETENTION_HOURS: i64 = 24;
+
+impl AgentCommand {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status.as_str(), "acked" | "failed" | "expired")
+    }
+
+    fn http_method(&self) -> Method {
+        Method::from_bytes(self.method.as_bytes()).unwrap_or(Method::POST)
+    }
+}
+
+/// 5s, 10s, 20s, ... capped at two minutes
+fn backoff(attempts: u32) -> chrono::Duration {
+    let secs = BACKOFF_BASE_SECS.saturating_mul(1 << attempts.saturating_sub(1).min(10));
+    chrono::Duration::seconds(secs.min(BACKOFF_MAX_SECS))
+}
+
+/// Store a command if it is still in the `expected` status; finished
+/// ones leave their host's queue. `false` if someone else changed it first.
+async fn save(state: &AppState, cmd: &AgentCommand, expected: &str) -> bool {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    match redis_store::replace_command(&mut conn, prefix, cmd, &[expected]).await {
+        Ok(true) => {}
+        Ok(false) => return false,
+        Err(e) => {
+            tracing::warn!("Could not store command {}: {e}", cmd.id);
+            return false;
+        }
+    }
+    if cmd.is_finished() || cmd.status == "delivered" {
+        let _ = redis_store::dequeue_command(&mut conn, prefix, &cmd.hostname, &cmd.id).await;
+    }
+    true
+}
+
+/// Take a command that is due for sending; `false` if another task has it.
+async fn claim(state: &AppState, cmd: &mut AgentCommand) -> bool {
+    let expected = std::mem::replace(&mut cmd.status, "sending".to_string());
+    cmd.next_attempt_at = Utc::now() + chrono::Duration::seconds(SEND_LEASE_SECS);
+    save(state, cmd, &expected).await
+}
+
+/// Tell the dashboards how a command that was not delivered at once turned out.
+fn announce(state: &AppState, cmd: &AgentCommand) {
+    state.notify_teachers(&json!({
+        "type": "command_status",
+        "id": cmd.id,
+        "hostname": cmd.hostname,
+        "path": cmd.path,
+        "status": cmd.status,
+        "attempts": cmd.attempts,
+        "error": cmd.last_error,
+    }));
+}
+
+/// Mark a command expired if its time is up.
+fn expire(cmd: &mut AgentCommand, now: DateTime<Utc>) -> bool {
+    if now < cmd.expires_at {
+        return false;
+    }
+    cmd.status = "expired".to_string();
+    tracing::info!("⌛ Command {} to {} expired after {} attempt(s)", cmd.id, cmd.hostname, cmd.attempts);
+    true
+}
+
+/// Try to deliver a claimed command once and record the outcome.
+async fn attempt(state: &AppState, cmd: &mut AgentCommand, agent: Option<&AgentAddr>) -> Reply {
+    let reply = agent_client::send_command_as(
+        state,
+        &cmd.id,
+        cmd.http_method(),
+        &cmd.hostname,
+        agent,
+        &cmd.path,
+        cmd.body.as_ref(),
+    )
+    .await;
+
+    let now = Utc::now();
+    cmd.attempts += 1;
+    match &reply {
+        Reply::Ok(body) => {
+            cmd.status = "acked".to_string();
+            cmd.delivered_at = Some(now);
+            cmd.acked_at = Some(now);
+            cmd.result = Some(body.clone());
+            cmd.last_error = None;
+        }
+        Reply::Status(status) => {
+            cmd.status = "failed".to_string();
+            cmd.last_error = Some(format!("Student returned {status}"));
+        }
+        Reply::Unreachable(e) => {
+            cmd.last_error = Some(e.clone());
+            if cmd.attempts >= state.config.command_max_attempts {
+                cmd.status = "failed".to_string();
+            } else if !expire(cmd, now) {
+                cmd.status = "pending".to_string();
+                cmd.next_attempt_at = now + backoff(cmd.attempts);
+            }
+        }
+    }
+    if !save(state, cmd, "sending").await {
+        tracing::info!("Command {} to {} was finished elsewhere", cmd.id, cmd.hostname);
+    }
+    reply
+}
+
+fn new_command(
+    state: &AppState,
+    created_by: &str,
+    agent: &AgentAddr,
+    method: Method,
+    path: &str,
+    body: Option<&Value>,
+) -> AgentCommand {
+    let now = Utc::now();
+    AgentCommand {
+        id: auth::new_token()[..16].to_string(),
+        hostname: agent.hostname.clone(),
+        method: method.as_str().to_string(),
+        path: path.to_string(),
+        body: body.cloned(),
+        status: "pending".to_string(),
+        attempts: 0,
+        created_by: created_by.to_string(),
+        created_at: now,
+        expires_at: now + chrono::Duration::seconds(state.config.command_ttl_secs),
+        next_attempt_at: now,
+        last_error: None,
+        delivered_at: None,
+        acked_at: None,
+        result: None,
+    }
+}
+
+/// Store a new command and put it in its host's queue.
+async fn store_new(state: &AppState, cmd: &AgentCommand) {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    let queued = redis_store::store_command(&mut conn, prefix, cmd).await.is_ok()
+        && redis_store::queue_command(&mut conn, prefix, &cmd.hostname, &cmd.id).await.is_ok();
+    if !queued {
+        tracing::warn!("Could not queue command {} to {}", cmd.id, cmd.hostname);
+    }
+}
+
+/// Queue a command for `agent` without trying to send it; the retry task,
+/// the agent's next heartbeat or its command channel will deliver it.
+pub async fn enqueue(
+    state: &AppState,
+    created_by: &str,
+    agent: &AgentAddr,
+    method: Method,
+    path: &str,
+    body: Option<&Value>,
+) -> AgentCommand {
+    let cmd = new_command(state, created_by, agent, method, path, body);
+    store_new(state, &cmd).await;
+    cmd
+}
+
+/// Queue a command for `agent` that is about to be sent by the caller.
+/// The retry task leaves it alone for `SEND_LEASE_SECS`; heartbeats and the
+/// command channel may still hand it out first.
+pub async fn prepare(
+    state: &AppState,
+    created_by: &str,
+    agent: &AgentAddr,
+    method: Method,
+    path: &str,
+    body: Option<&Value>,
+) -> AgentCommand {
+    let mut cmd = new_command(state, created_by, agent, method, path, body);
+    cmd.next_attempt_at = cmd.created_at + chrono::Duration::seconds(SEND_LEASE_SECS);
+    store_new(state, &cmd).await;
+    cmd
+}
+
+/// Claim a command from `prepare` and try it on `agent`; it is left
+/// "pending" if the agent was unreachable. `None` if it was already
+/// handed out some other way.
+pub async fn send_prepared(state: &AppState, cmd: &mut AgentCommand, agent: &AgentAddr) -> Option<Reply> {
+    if !claim(state, cmd).await {
+        return None;
+    }
+    Some(attempt(state, cmd, Some(agent)).await)
+}
+
+/// Queue a command for `agent` and try to deliver it straight away.
+/// The returned command is still "pending" if the agent was unreachable.
+pub async fn submit(
+    state: &AppState,
+    created_by: &str,
+    agent: &AgentAddr,
+    method: Method,
+    path: &str,
+    body: Option<&Value>,
+) -> (AgentCommand, Reply) {
+    // Stored already claimed, so nothing else sends it in the meantime
+    let mut cmd = new_command(state, created_by, agent, method, path, body);
+    cmd.status = "sending".to_string();
+    cmd.next_attempt_at = cmd.created_at + chrono::Duration::seconds(SEND_LEASE_SECS);
+    store_new(state, &cmd).await;
+    let reply = attempt(state, &mut cmd, Some(agent)).await;
+    (cmd, reply)
+}
+
+/// Pending commands for `hostname`, oldest first
+async fn pending_for(state: &AppState, hostname: &str) -> Vec<AgentCommand> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    let ids = redis_store::get_queued_commands(&mut conn, prefix, hostname)
+        .await
+        .unwrap_or_default();
+
+    let mut pending = Vec::new();
+    for id in ids {
+        match redis_store::get_command(&mut conn, prefix, &id).await {
+            Ok(Some(cmd)) if cmd.status == "pending" => pending.push(cmd),
+            // Stale entry: finished elsewhere, or pruned
+            Ok(_) => {
+                let _ = redis_store::dequeue_command(&mut conn, prefix, hostname, &id).await;
+            }
+            Err(_) => {}
+        }
+    }
+    pending
+}
+
+/// Send everything queued for `hostname`, in order, until one fails.
+/// Run when the agent opens its command channel.
+pub async fn flush(state: Arc<AppState>, hostname: String) {
+    for mut cmd in pending_for(&state, &hostname).await {
+        if expire(&mut cmd, Utc::now()) {
+            if save(&state, &cmd, "pending").await {
+                announce(&state, &cmd);
+            }
+            continue;
+        }
+        if !claim(&state, &mut cmd).await {
+            continue;
+        }
+        let reply = attempt(&state, &mut cmd, None).await;
+        announce(&state, &cmd);
+        if matches!(reply, Reply::Unreachable(_)) {
+            break;
+        }
+    }
+}
+
+/// Signed frames for everything queued for `hostname`, to return from its
+/// heartbeat. They count as delivered; the agent acks each one after
+/// running it. Agents with a channel open get theirs over the channel.
+pub async fn take_for_heartbeat(state: &Arc<AppState>, hostname: &str) -> Vec<Value> {
+    if state.agent_channels.contains_key(hostname) {
+        tokio::spawn(flush(state.clone(), hostname.to_string()));
+        return Vec::new();
+    }
+
+    let now = Utc::now();
+    let mut frames = Vec::new();
+    for mut cmd in pending_for(state, hostname).await {
+        if !expire(&mut cmd, now) {
+            cmd.status = "delivered".to_string();
+            cmd.attempts += 1;
+            cmd.delivered_at = Some(now);
+        }
+        if !save(state, &cmd, "pending").await {
+            continue;
+        }
+        if cmd.status == "delivered" {
+            frames.push(agent_channel::frame(
+                state,
+                &cmd.id,
+                hostname,
+                &cmd.method,
+                &cmd.path,
+                cmd.body.as_ref(),
+            ));
+        }
+        announce(state, &cmd);
+    }
+    frames
+}
+
+/// Record the agent's answer to a command it got with a heartbeat.
+/// `false` if the command is unknown, not the agent's, or already finished.
+pub async fn ack(state: &AppState, hostname: &str, id: &str, status: u16, body: Value) -> redis::RedisResult<bool> {
+    let mut conn = state.redis.clone();
+    let Some(mut cmd) = redis_store::get_command(&mut conn, &state.config.key_prefix, id).await? else {
+        return Ok(false);
+    };
+    if cmd.hostname != hostname || cmd.is_finished() {
+        return Ok(false);
+    }
+    let seen = cmd.status.clone();
+
+    let now = Utc::now();
+    cmd.delivered_at.get_or_insert(now);
+    if (200..300).contains(&status) {
+        cmd.status = "acked".to_string();
+        cmd.acked_at = Some(now);
+        cmd.last_error = None;
+    } else {
+        cmd.status = "failed".to_string();
+        cmd.last_error = Some(format!("Student returned {status}"));
+    }
+    cmd.result = Some(body);
+    if !save(state, &cmd, &seen).await {
+        return Ok(false);
+    }
+    announce(state, &cmd);
+    Ok(true)
+}
+
+/// Background task: retry due commands, expire stale ones and prune
+/// finished ones past retention.
+pub async fn retry_task(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    loop {
+        interval.tick().await;
+
+        let mut conn = state.redis.clone();
+        let prefix = &state.config.key_prefix;
+        let Ok(mut all) = redis_store::get_all_commands(&mut conn, prefix).await else { continue };
+        all.sort_by_key(|c| c.created_at);
+
+        let registry: Vec<AgentAddr> = redis_store::get_all_agents(&mut conn, prefix)
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|e| AgentAddr::parse(e))
+            .collect();
+
+        let now = Utc::now();
+        let retention = now - chrono::Duration::hours(RETENTION_HOURS);
+        // Hosts that failed this round; their later commands wait their turn
+        let mut unreachable = HashSet::new();
+        for mut cmd in all {
+            if cmd.is_finished() {
+                if cmd.created_at < retention {
+                    let _ = redis_store::delete_command(&mut conn, prefix, &cmd.id).await;
+                }
+                continue;
+            }
+            // Delivered with a heartbeat but never acked
+            if cmd.status == "delivered" {
+                if expire(&mut cmd, now) && save(&state, &cmd, "delivered").await {
+                    announce(&state, &cmd);
+                }
+                continue;
+            }
+            // Pending and due, or claimed by a send that never finished
+            if cmd.next_attempt_at > now || unreachable.contains(&cmd.hostname) {
+                continue;
+            }
+            let seen = cmd.status.clone();
+            if expire(&mut cmd, now) {
+                if save(&state, &cmd, &seen).await {
+                    announce(&state, &cmd);
+                }
+                continue;
+            }
+            if !claim(&state, &mut cmd).await {
+                continue;
+            }
+
+            let agent = registry.iter().find(|a| a.hostname == cmd.hostname);
+            let reply = attempt(&state, &mut cmd, agent).await;
+            if let Reply::Unreachable(_) = reply {
+                unreachable.insert(cmd.hostname.clone());
+                if !cmd.is_finished() {
+                    continue;
+                }
+            }
+            tracing::info!("📬 Queued command {} to {}: {}", cmd.id, cmd.hostname, cmd.status);
+            announce(&state, &cmd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_two_minutes() {
+        let secs: Vec<i64> = (0..=8).map(|n| backoff(n).num_seconds()).collect();
+        assert_eq!(secs, vec![5, 5, 10, 20, 40, 80, 120, 120, 120]);
+        assert_eq!(backoff(u32::MAX).num_seconds(), BACKOFF_MAX_SECS);
+    }
+}
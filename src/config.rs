@@ -1,5 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
+use toml_edit::{Array, DocumentMut, Item, Value};
+
+use crate::models::BanPolicy;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -12,6 +16,234 @@ pub struct Config {
     pub banned_apps: Vec<String>,
     #[serde(default)]
     pub sau_mode: bool,
+    /// Exam mode: only `allowed_sites` / `allowed_apps` may be used
+    #[serde(default)]
+    pub exam_mode: bool,
+    #[serde(default)]
+    pub allowed_sites: Vec<String>,
+    #[serde(default)]
+    pub allowed_apps: Vec<String>,
+    /// How long a teacher login stays valid
+    #[serde(default = "default_session_ttl_secs")]
+    pub session_ttl_secs: u64,
+    /// Failed logins allowed per username before it is locked out
+    #[serde(default = "default_login_max_failures")]
+    pub login_max_failures: u32,
+    /// Failed logins allowed per client address before it is locked out
+    #[serde(default = "default_login_max_failures_per_ip")]
+    pub login_max_failures_per_ip: u32,
+    /// How long failed logins are counted, and so how long a lockout lasts
+    #[serde(default = "default_login_lockout_secs")]
+    pub login_lockout_secs: u64,
+    /// Password for the bootstrap "admin" account, created when no teacher
+    /// accounts exist yet. Left empty, a random one is generated and logged.
+    /// Never serialized, so it is not exposed by GET /api/config.
+    #[serde(default, skip_serializing)]
+    pub admin_password: String,
+    /// Maximum clock skew accepted on signed agent requests
+    #[serde(default = "default_agent_signature_window_secs")]
+    pub agent_signature_window_secs: u64,
+    /// Where the backend's command-signing key is kept
+    #[serde(default = "default_signing_key_path")]
+    pub signing_key_path: String,
+    /// Serve HTTPS/WSS instead of plain HTTP on `port`
+    #[serde(default)]
+    pub tls_enabled: bool,
+    /// PEM certificate and key; a self-signed pair is generated if missing
+    #[serde(default = "default_tls_cert_path")]
+    pub tls_cert_path: String,
+    #[serde(default = "default_tls_key_path")]
+    pub tls_key_path: String,
+    /// Sustained requests per second allowed from one agent
+    #[serde(default = "default_agent_rate_per_sec")]
+    pub agent_rate_per_sec: f64,
+    /// Requests an agent may send in a burst before being throttled
+    #[serde(default = "default_agent_rate_burst")]
+    pub agent_rate_burst: u32,
+    /// Body size caps in bytes per agent endpoint (e.g. `screenshot`)
+    #[serde(default = "default_agent_body_limits")]
+    pub agent_body_limits: HashMap<String, usize>,
+    /// Cap for agent endpoints not listed in `agent_body_limits`
+    #[serde(default = "default_agent_body_limit")]
+    pub agent_body_limit: usize,
+    /// Origins allowed to call the API cross-origin; empty means same-origin only
+    #[serde(default)]
+    pub cors_allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_allowed_methods")]
+    pub cors_allowed_methods: Vec<String>,
+    #[serde(default)]
+    pub cors_allow_credentials: bool,
+    /// Content-Security-Policy sent with every response
+    #[serde(default = "default_content_security_policy")]
+    pub content_security_policy: String,
+    /// How long an approved access request unblocks the item
+    #[serde(default = "default_access_grant_secs")]
+    pub access_grant_secs: i64,
+    /// Requests a student machine may have waiting at once
+    #[serde(default = "default_access_requests_max_pending")]
+    pub access_requests_max_pending: usize,
+    /// New requests a student machine may send per hour
+    #[serde(default = "default_access_requests_per_hour")]
+    pub access_requests_per_hour: u32,
+    /// Longest a per-student override may last; also the lifetime of one
+    /// created without an expiry
+    #[serde(default = "default_override_max_secs")]
+    pub override_max_secs: i64,
+    /// Audit entries older than this are dropped as new ones are written
+    #[serde(default = "default_audit_retention_days")]
+    pub audit_retention_days: i64,
+    /// A command from another teacher to the same student within this
+    /// window counts as a conflict
+    #[serde(default = "default_conflict_window_secs")]
+    pub conflict_window_secs: i64,
+    /// How long an undelivered command is retried before it expires
+    #[serde(default = "default_command_ttl_secs")]
+    pub command_ttl_secs: i64,
+    /// Delivery attempts before a command is marked failed
+    #[serde(default = "default_command_max_attempts")]
+    pub command_max_attempts: u32,
+    /// Students a multi-host command is sent to at once
+    #[serde(default = "default_fanout_concurrency")]
+    pub fanout_concurrency: usize,
+    /// Longest a multi-host command waits for answers before replying
+    #[serde(default = "default_fanout_deadline_secs")]
+    pub fanout_deadline_secs: u64,
+    /// Named ban profiles the timetable can switch to (e.g. `lunch`)
+    #[serde(default)]
+    pub policy_profiles: HashMap<String, BanPolicy>,
+    /// Weekly timetable; outside every slot the lists above apply
+    #[serde(default)]
+    pub schedule: Vec<ScheduleSlot>,
+}
+
+/// One timetable entry, in the server's local time:
+///
+/// ```toml
+/// [[schedule]]
+/// days = ["mon", "tue", "wed", "thu", "fri"]
+/// start = "12:00"
+/// end = "12:45"
+/// profile = "lunch"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleSlot {
+    pub days: Vec<String>,
+    /// "HH:MM", inclusive
+    pub start: String,
+    /// "HH:MM", exclusive
+    pub end: String,
+    pub profile: String,
+}
+
+impl Config {
+    /// Body cap for the agent endpoint named `endpoint`
+    pub fn agent_body_limit_for(&self, endpoint: &str) -> usize {
+        self.agent_body_limits
+            .get(endpoint)
+            .copied()
+            .unwrap_or(self.agent_body_limit)
+    }
+}
+
+fn default_access_grant_secs() -> i64 {
+    45 * 60
+}
+
+fn default_access_requests_max_pending() -> usize {
+    5
+}
+
+fn default_access_requests_per_hour() -> u32 {
+    10
+}
+
+fn default_override_max_secs() -> i64 {
+    7 * 24 * 3600
+}
+
+fn default_audit_retention_days() -> i64 {
+    365
+}
+
+fn default_conflict_window_secs() -> i64 {
+    60
+}
+
+fn default_command_ttl_secs() -> i64 {
+    15 * 60
+}
+
+fn default_command_max_attempts() -> u32 {
+    20
+}
+
+fn default_fanout_concurrency() -> usize {
+    16
+}
+
+fn default_fanout_deadline_secs() -> u64 {
+    8
+}
+
+fn default_agent_rate_per_sec() -> f64 {
+    2.0
+}
+
+fn default_agent_rate_burst() -> u32 {
+    20
+}
+
+fn default_agent_body_limits() -> HashMap<String, usize> {
+    HashMap::from([
+        ("screenshot".to_string(), 2 * 1024 * 1024),
+        ("apps".to_string(), 256 * 1024),
+    ])
+}
+
+fn default_agent_body_limit() -> usize {
+    16 * 1024
+}
+
+fn default_cors_allowed_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec()
+}
+
+fn default_content_security_policy() -> String {
+    "default-src 'self'; img-src 'self' data: blob:; connect-src 'self' ws: wss:; \
+     style-src 'self' 'unsafe-inline'; object-src 'none'; frame-ancestors 'none'"
+        .to_string()
+}
+
+fn default_tls_cert_path() -> String {
+    "tls/cert.pem".to_string()
+}
+
+fn default_tls_key_path() -> String {
+    "tls/key.pem".to_string()
+}
+
+fn default_signing_key_path() -> String {
+    "server.key".to_string()
+}
+
+fn default_agent_signature_window_secs() -> u64 {
+    60
+}
+
+fn default_session_ttl_secs() -> u64 {
+    12 * 60 * 60
+}
+
+fn default_login_max_failures() -> u32 {
+    5
+}
+
+fn default_login_max_failures_per_ip() -> u32 {
+    20
+}
+
+fn default_login_lockout_secs() -> u64 {
+    15 * 60
 }
 
 impl Default for Config {
@@ -25,6 +257,39 @@ impl Default for Config {
             banned_sites: vec![],
             banned_apps: vec![],
             sau_mode: false,
+            exam_mode: false,
+            allowed_sites: vec![],
+            allowed_apps: vec![],
+            session_ttl_secs: default_session_ttl_secs(),
+            login_max_failures: default_login_max_failures(),
+            login_max_failures_per_ip: default_login_max_failures_per_ip(),
+            login_lockout_secs: default_login_lockout_secs(),
+            admin_password: String::new(),
+            agent_signature_window_secs: default_agent_signature_window_secs(),
+            signing_key_path: default_signing_key_path(),
+            tls_enabled: false,
+            tls_cert_path: default_tls_cert_path(),
+            tls_key_path: default_tls_key_path(),
+            agent_rate_per_sec: default_agent_rate_per_sec(),
+            agent_rate_burst: default_agent_rate_burst(),
+            agent_body_limits: default_agent_body_limits(),
+            agent_body_limit: default_agent_body_limit(),
+            cors_allowed_origins: vec![],
+            cors_allowed_methods: default_cors_allowed_methods(),
+            cors_allow_credentials: false,
+            content_security_policy: default_content_security_policy(),
+            access_grant_secs: default_access_grant_secs(),
+            access_requests_max_pending: default_access_requests_max_pending(),
+            access_requests_per_hour: default_access_requests_per_hour(),
+            override_max_secs: default_override_max_secs(),
+            audit_retention_days: default_audit_retention_days(),
+            conflict_window_secs: default_conflict_window_secs(),
+            command_ttl_secs: default_command_ttl_secs(),
+            command_max_attempts: default_command_max_attempts(),
+            fanout_concurrency: default_fanout_concurrency(),
+            fanout_deadline_secs: default_fanout_deadline_secs(),
+            policy_profiles: HashMap::new(),
+            schedule: vec![],
         }
     }
 }
@@ -41,3 +306,86 @@ pub fn load_config() -> Config {
         }
     }
 }
+
+/// Write the ban lists into config.toml. Only keys whose value changed
+/// are touched, so comments, layout and settings that are never
+/// serialized (such as `admin_password`) are kept.
+pub fn save_policy(policy: &BanPolicy) -> Result<(), String> {
+    let current = fs::read_to_string("config.toml").unwrap_or_default();
+    let updated = with_policy(&current, policy).map_err(|e| format!("config.toml: {e}"))?;
+    fs::write("config.toml", updated).map_err(|e| format!("config.toml: {e}"))
+}
+
+fn with_policy(text: &str, policy: &BanPolicy) -> Result<String, toml_edit::TomlError> {
+    let mut doc: DocumentMut = text.parse()?;
+    set_list(&mut doc, "banned_sites", &policy.banned_sites);
+    set_list(&mut doc, "banned_apps", &policy.banned_apps);
+    set_bool(&mut doc, "sau_mode", policy.sau_mode);
+    set_bool(&mut doc, "exam_mode", policy.exam_mode);
+    set_list(&mut doc, "allowed_sites", &policy.allowed_sites);
+    set_list(&mut doc, "allowed_apps", &policy.allowed_apps);
+    Ok(doc.to_string())
+}
+
+fn set_bool(doc: &mut DocumentMut, key: &str, flag: bool) {
+    match doc.get_mut(key).and_then(Item::as_value_mut) {
+        Some(old) if old.as_bool() == Some(flag) => {}
+        Some(old) => {
+            let decor = old.decor().clone();
+            *old = Value::from(flag);
+            *old.decor_mut() = decor;
+        }
+        None => doc[key] = toml_edit::value(flag),
+    }
+}
+
+/// Lists that were written one item per line stay that way
+fn set_list(doc: &mut DocumentMut, key: &str, items: &[String]) {
+    let mut list: Array = items.iter().map(String::as_str).collect();
+    match doc.get_mut(key).and_then(Item::as_value_mut) {
+        Some(Value::Array(old)) if old.iter().map(Value::as_str).eq(items.iter().map(|i| Some(i.as_str()))) => {}
+        Some(old) => {
+            if old.to_string().contains('\n') && !list.is_empty() {
+                for item in list.iter_mut() {
+                    item.decor_mut().set_prefix("\n    ");
+                }
+                list.set_trailing("\n");
+                list.set_trailing_comma(true);
+            }
+            let decor = old.decor().clone();
+            *old = Value::Array(list);
+            *old.decor_mut() = decor;
+        }
+        None => doc[key] = toml_edit::value(list),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_the_policy_keeps_other_settings_and_comments() {
+        let text = "port = 8080\n\
+            # bootstrap login\n\
+            admin_password = \"s3cret\"\n\
+            banned_sites = [\n    \"youtube.com\",\n]\n\
+            # Exam mode comment\n\
+            exam_mode = false\n\
+            allowed_apps = []\n";
+        let policy = BanPolicy {
+            banned_sites: vec!["youtube.com".into(), "tiktok.com".into()],
+            exam_mode: true,
+            ..BanPolicy::default()
+        };
+
+        let saved = with_policy(text, &policy).unwrap();
+        assert!(saved.contains("# bootstrap login\nadmin_password = \"s3cret\"\n"));
+        assert!(saved.contains("# Exam mode comment\nexam_mode = true\n"));
+        assert!(saved.contains("banned_sites = [\n    \"youtube.com\",\n    \"tiktok.com\",\n]\n"));
+        assert!(saved.contains("allowed_apps = []\n"));
+
+        // Nothing changed, nothing rewritten
+        assert_eq!(with_policy(&saved, &policy).unwrap(), saved);
+    }
+}
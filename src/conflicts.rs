@@ -0,0 +1,94 @@
+// ─────────────────────────────────────────────────────────────────
+//  conflicts.rs — Teachers working against each other
+//
+//  The last command of each kind sent to each student is remembered.
+//  A command that contradicts one another teacher sent to the same
+//  student within `conflict_window_secs` (a different lock mode, a
+//  different URL) is refused with the details, and goes through
+//  only when resent with "force": true; the other teachers are then
+//  told their command was overridden.
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::auth::Identity;
+use crate::state::{AppState, RecentCommand};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Conflict {
+    pub hostname: String,
+    pub kind: String,
+    /// What the other teacher asked for
+    pub value: String,
+    pub by: String,
+    pub at: String,
+}
+
+/// Recent commands from other teachers that `value` would contradict
+pub fn find(state: &AppState, identity: &Identity, hosts: &[String], kind: &str, value: &str) -> Vec<Conflict> {
+    let since = Utc::now() - chrono::Duration::seconds(state.config.conflict_window_secs);
+    hosts
+        .iter()
+        .filter_map(|h| {
+            let last = state.recent_commands.get(&(h.clone(), kind.to_string()))?;
+            (last.by != identity.username && last.value != value && last.at > since).then(|| Conflict {
+                hostname: h.clone(),
+                kind: kind.to_string(),
+                value: last.value.clone(),
+                by: last.by.clone(),
+                at: last.at.to_rfc3339(),
+            })
+        })
+        .collect()
+}
+
+/// The response refusing the command, or `None` to go ahead. Forced
+/// commands are let through and the overridden teachers notified.
+pub fn guard(
+    state: &AppState,
+    identity: &Identity,
+    hosts: &[String],
+    kind: &str,
+    value: &str,
+    force: bool,
+) -> Option<Value> {
+    let conflicts = find(state, identity, hosts, kind, value);
+    if conflicts.is_empty() {
+        return None;
+    }
+    if !force {
+        tracing::info!("⚔️ {} {kind} refused: conflicts on {} student(s)", identity.username, conflicts.len());
+        return Some(json!({
+            "status": "conflict",
+            "error": "Another teacher just sent a different command to some of these students. Resend with \"force\": true to override.",
+            "conflicts": conflicts,
+        }));
+    }
+
+    tracing::info!("⚔️ {} overrode {kind} on {} student(s)", identity.username, conflicts.len());
+    state.notify_teachers(&json!({
+        "type": "command_overridden",
+        "by": identity.username,
+        "kind": kind,
+        "value": value,
+        "conflicts": conflicts,
+    }));
+    None
+}
+
+/// Remember a command that was sent.
+pub fn record(state: &AppState, identity: &Identity, hosts: &[String], kind: &str, value: &str) {
+    let now = Utc::now();
+    for h in hosts {
+        state.recent_commands.insert(
+            (h.clone(), kind.to_string()),
+            RecentCommand {
+                value: value.to_string(),
+                by: identity.username.clone(),
+                at: now,
+            },
+        );
+    }
+}
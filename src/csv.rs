@@ -0,0 +1,116 @@
+// ─────────────────────────────────────────────────────────────────
+//  csv.rs — Minimal RFC 4180 reading and writing
+//
+//  Enough for roster imports and report exports: quoted fields,
+//  doubled quotes, commas and line breaks inside quotes, CRLF.
+//  Written fields that a spreadsheet would run as a formula are
+//  prefixed with ' so they open as text.
+// ─────────────────────────────────────────────────────────────────
+
+/// Split `text` into records of fields. Blank lines are skipped.
+pub fn parse(text: &str) -> Vec<Vec<String>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|f| !f.is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    record.push(field);
+    if record.iter().any(|f| !f.is_empty()) {
+        records.push(record);
+    }
+    records
+}
+
+fn escape(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{field}")
+    } else {
+        field.to_string()
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+/// Append one record (CRLF-terminated) to `out`.
+pub fn write_row<S: AsRef<str>>(out: &mut String, fields: &[S]) {
+    let line: Vec<String> = fields.iter().map(|f| escape(f.as_ref())).collect();
+    out.push_str(&line.join(","));
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_fields_and_line_endings() {
+        let text = "\u{feff}id,name\r\n1,\"Nguyen, An\"\r\n\r\n2,\"say \"\"hi\"\"\nthere\"\n3,";
+        assert_eq!(
+            parse(text),
+            vec![
+                vec!["id", "name"],
+                vec!["1", "Nguyen, An"],
+                vec!["2", "say \"hi\"\nthere"],
+                vec!["3", ""],
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_blank_lines_and_empty_input() {
+        assert!(parse("").is_empty());
+        assert_eq!(parse("\n,\na\n\n"), vec![vec!["a"]]);
+    }
+
+    #[test]
+    fn escape_quotes_only_when_needed() {
+        assert_eq!(escape("LAB2-PC07"), "LAB2-PC07");
+        assert_eq!(escape(""), "");
+        assert_eq!(escape("a,b"), "\"a,b\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn formulas_are_written_as_text() {
+        assert_eq!(escape("=HYPERLINK(\"http://x\")"), "\"'=HYPERLINK(\"\"http://x\"\")\"");
+        assert_eq!(escape("+1"), "'+1");
+        assert_eq!(escape("-cmd"), "'-cmd");
+        assert_eq!(escape("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(escape("youtube.com"), "youtube.com");
+    }
+
+    #[test]
+    fn written_rows_parse_back() {
+        let mut out = String::new();
+        write_row(&mut out, &["plain", "a,b", "q\"uote", "two\nlines"]);
+        assert_eq!(parse(&out), vec![vec!["plain", "a,b", "q\"uote", "two\nlines"]]);
+    }
+}
@@ -0,0 +1,181 @@
+// ─────────────────────────────────────────────────────────────────
+//  exam.rs — Server-side allowlist checks for exam mode
+//
+//  Every app list an agent posts is compared against the student's
+//  allowlist. Anything outside it becomes a high-severity violation,
+//  raised once when it appears and again only after it has gone
+//  away and come back. The list is every running process, so the
+//  operating system's own, the NisHack agent and browsers (judged by
+//  their tabs instead) are never flagged.
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::Utc;
+use dashmap::DashMap;
+use std::collections::HashSet;
+
+use crate::models::{AppList, BanPolicy, Violation};
+
+fn normalize_app(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    name.strip_suffix(".exe").map(str::to_string).unwrap_or(name)
+}
+
+/// Processes every Windows machine runs, lowercased without ".exe"
+const SYSTEM_PROCESSES: &[&str] = &[
+    "system", "system idle process", "idle", "registry", "memory compression", "secure system",
+    "smss", "csrss", "wininit", "winlogon", "services", "lsass", "lsaiso", "svchost", "fontdrvhost",
+    "dwm", "explorer", "sihost", "taskhostw", "ctfmon", "conhost", "dllhost", "runtimebroker",
+    "spoolsv", "audiodg", "wudfhost", "wmiprvse", "searchhost", "searchindexer", "searchapp",
+    "searchui", "startmenuexperiencehost", "shellexperiencehost", "textinputhost",
+    "applicationframehost", "systemsettings", "securityhealthservice", "securityhealthsystray",
+    "msmpeng", "nissrv", "mpdefendercoreservice", "smartscreen", "sgrmbroker", "lockapp",
+    "userinit", "taskmgr", "backgroundtaskhost", "useroobebroker", "widgets", "phoneexperiencehost",
+];
+
+/// Browsers; what they show is checked against the site allowlist
+const BROWSERS: &[&str] = &["msedge", "chrome", "firefox", "brave", "opera", "vivaldi", "iexplore"];
+
+/// Processes an exam never flags
+fn exempt(name: &str) -> bool {
+    let name = normalize_app(name);
+    name.starts_with("nishack") || SYSTEM_PROCESSES.contains(&name.as_str()) || BROWSERS.contains(&name.as_str())
+}
+
+fn app_allowed(policy: &BanPolicy, name: &str) -> bool {
+    let name = normalize_app(name);
+    policy.allowed_apps.iter().any(|a| normalize_app(a) == name)
+}
+
+/// A domain is allowed if it or any parent domain is listed.
+fn site_allowed(policy: &BanPolicy, host: &str) -> bool {
+    let host = host.trim_start_matches("www.").to_lowercase();
+    policy.allowed_sites.iter().any(|s| {
+        let s = s.trim().trim_start_matches("www.").to_lowercase();
+        host == s || host.ends_with(&format!(".{s}"))
+    })
+}
+
+/// Violations for apps and tabs outside the allowlist that were not
+/// already flagged on the previous report (`flags`, see `AppState::exam_flags`).
+pub fn check(
+    flags: &DashMap<String, HashSet<(String, String)>>,
+    policy: &BanPolicy,
+    apps: &AppList,
+) -> Vec<Violation> {
+    if !policy.exam_mode {
+        flags.remove(&apps.hostname);
+        return Vec::new();
+    }
+
+    let mut current: HashSet<(String, String)> = HashSet::new();
+    for app in &apps.applications {
+        if !exempt(&app.name) && !app_allowed(policy, &app.name) {
+            current.insert(("exam_app".to_string(), app.name.clone()));
+        }
+    }
+    for tab in &apps.browser_tabs {
+        let Ok(url) = reqwest::Url::parse(&tab.url) else { continue };
+        let Some(host) = url.host_str() else { continue };
+        if !site_allowed(policy, host) {
+            current.insert(("exam_site".to_string(), host.to_string()));
+        }
+    }
+
+    let previous = flags.insert(apps.hostname.clone(), current.clone()).unwrap_or_default();
+    let mut fresh: Vec<_> = current.difference(&previous).cloned().collect();
+    fresh.sort();
+
+    fresh
+        .into_iter()
+        .map(|(rule, detail)| Violation {
+            hostname: apps.hostname.clone(),
+            rule,
+            detail,
+            severity: "high".to_string(),
+            timestamp: Utc::now(),
+            session_id: apps.session_id.clone(),
+            student_id: None,
+            student_name: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Application, BrowserTab};
+
+    fn exam_policy() -> BanPolicy {
+        BanPolicy {
+            exam_mode: true,
+            allowed_apps: vec!["Word.exe".into()],
+            allowed_sites: vec!["www.moodle.org".into()],
+            ..BanPolicy::default()
+        }
+    }
+
+    fn report(apps: &[&str], urls: &[&str]) -> AppList {
+        AppList {
+            hostname: "LAB2-PC07".into(),
+            applications: apps
+                .iter()
+                .map(|name| Application { name: name.to_string(), pid: 1, memory_mb: 0.0 })
+                .collect(),
+            browser_tabs: urls
+                .iter()
+                .map(|url| BrowserTab { browser: "edge".into(), title: String::new(), url: url.to_string() })
+                .collect(),
+            timestamp: Utc::now(),
+            session_id: None,
+        }
+    }
+
+    fn details(violations: &[Violation]) -> Vec<(&str, &str)> {
+        violations.iter().map(|v| (v.rule.as_str(), v.detail.as_str())).collect()
+    }
+
+    #[test]
+    fn flags_what_is_outside_the_allowlist() {
+        let flags = DashMap::new();
+        let apps = report(
+            &["word", "Discord.exe"],
+            &["https://exam.moodle.org/quiz", "https://www.youtube.com/", "not a url"],
+        );
+        let found = check(&flags, &exam_policy(), &apps);
+        assert_eq!(details(&found), vec![("exam_app", "Discord.exe"), ("exam_site", "www.youtube.com")]);
+        assert!(found.iter().all(|v| v.severity == "high"));
+    }
+
+    #[test]
+    fn system_processes_the_agent_and_browsers_are_not_flagged() {
+        let flags = DashMap::new();
+        let processes = [
+            "System Idle Process", "System", "Registry", "smss.exe", "csrss.exe", "wininit.exe",
+            "services.exe", "lsass.exe", "svchost.exe", "svchost.exe", "fontdrvhost.exe", "dwm.exe",
+            "explorer.exe", "sihost.exe", "taskhostw.exe", "ctfmon.exe", "RuntimeBroker.exe",
+            "SearchHost.exe", "StartMenuExperienceHost.exe", "TextInputHost.exe", "MsMpEng.exe",
+            "conhost.exe", "nishack-agent.exe", "msedge.exe", "msedge.exe", "WINWORD.EXE", "Word.exe",
+            "Spotify.exe",
+        ];
+        let found = check(&flags, &exam_policy(), &report(&processes, &["https://moodle.org/"]));
+        assert_eq!(details(&found), vec![("exam_app", "Spotify.exe"), ("exam_app", "WINWORD.EXE")]);
+    }
+
+    #[test]
+    fn raises_each_finding_once_until_it_goes_away() {
+        let flags = DashMap::new();
+        let policy = exam_policy();
+        assert_eq!(check(&flags, &policy, &report(&["Discord"], &[])).len(), 1);
+        assert!(check(&flags, &policy, &report(&["Discord"], &[])).is_empty());
+        assert!(check(&flags, &policy, &report(&[], &[])).is_empty());
+        assert_eq!(check(&flags, &policy, &report(&["Discord"], &[])).len(), 1);
+    }
+
+    #[test]
+    fn nothing_outside_exam_mode() {
+        let flags = DashMap::new();
+        let policy = BanPolicy { exam_mode: false, ..exam_policy() };
+        assert!(check(&flags, &policy, &report(&["Discord"], &[])).is_empty());
+        assert!(flags.is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+// ─────────────────────────────────────────────────────────────────
+//  fanout.rs — One command, many students, at once
+//
+//  Every multi-host command (broadcasts, group / classroom / selected
+//  hostnames) goes through `send`. Each host's command is queued first
+//  (see commands.rs), then:
+//    • offline hosts (no heartbeat, no command channel) are skipped
+//      and wait in the queue rather than tie up a slot timing out,
+//    • up to `fanout_concurrency` hosts are tried at the same time,
+//    • whatever hasn't answered by `fanout_deadline_secs` is reported
+//      as timed out. Each host is sent in its own task, so those sends
+//      still finish and record their outcome after the response.
+//  One result per hostname: ok | error (with the agent's HTTP status)
+//  | unreachable | skipped_offline | timeout | not_found.
+// ─────────────────────────────────────────────────────────────────
+
+use reqwest::Method;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+
+use crate::agent_client::{AgentAddr, Reply};
+use crate::commands;
+use crate::models::{AgentCommand, AuditResult};
+use crate::redis_store;
+use crate::state::AppState;
+use crate::targets::Resolved;
+
+/// The per-host audit result for a command that was just tried.
+pub fn result(cmd: &AgentCommand, reply: &Reply) -> AuditResult {
+    let hostname = cmd.hostname.as_str();
+    let result = match reply {
+        Reply::Ok(_) => AuditResult::ok(hostname),
+        Reply::Status(status) => AuditResult::http_error(hostname, *status),
+        Reply::Unreachable(e) if cmd.status == "pending" => {
+            let detail = format!("Cannot reach student: {e}; queued until {}", cmd.expires_at.to_rfc3339());
+            AuditResult::unreachable(hostname, &detail)
+        }
+        Reply::Unreachable(e) => AuditResult::error(hostname, &format!("Cannot reach student: {e}")),
+    };
+    result.with_command(&cmd.id)
+}
+
+/// A heartbeat that hasn't expired or an open command channel.
+/// Assumed online when Redis can't tell.
+async fn is_online(state: &AppState, hostname: &str) -> bool {
+    if state.agent_channels.contains_key(hostname) {
+        return true;
+    }
+    let mut conn = state.redis.clone();
+    redis_store::get_heartbeat(&mut conn, &state.config.key_prefix, hostname)
+        .await
+        .map_or(true, |hb| hb.is_some())
+}
+
+/// What a host's send task reports back
+enum Progress {
+    /// The command is stored; if the send outlasts the deadline, this is
+    /// the command the timeout refers to
+    Queued(String, String),
+    Done(AuditResult),
+}
+
+/// What the host send tasks of one fanout share
+#[derive(Clone)]
+struct Lanes {
+    slots: Arc<Semaphore>,
+    progress: mpsc::UnboundedSender<Progress>,
+}
+
+/// Queue the command for one host, then send it once a slot is free.
+/// Queued before waiting, so a host still waiting at the deadline has a
+/// command to report.
+async fn send_one(
+    state: &AppState,
+    created_by: &str,
+    agent: &AgentAddr,
+    method: Method,
+    path: &str,
+    payload: Option<&Value>,
+    lanes: &Lanes,
+) -> AuditResult {
+    if !is_online(state, &agent.hostname).await {
+        let cmd = commands::enqueue(state, created_by, agent, method, path, payload).await;
+        return AuditResult::skipped_offline(&agent.hostname).with_command(&cmd.id);
+    }
+    let mut cmd = commands::prepare(state, created_by, agent, method, path, payload).await;
+    let _ = lanes.progress.send(Progress::Queued(agent.hostname.clone(), cmd.id.clone()));
+
+    let _slot = lanes.slots.acquire().await;
+    match commands::send_prepared(state, &mut cmd, agent).await {
+        Some(reply) => result(&cmd, &reply),
+        // Handed out with a heartbeat or over the channel meanwhile
+        None => AuditResult::timeout(&agent.hostname).with_command(&cmd.id),
+    }
+}
+
+/// Queue and send the same command to every resolved agent, concurrently,
+/// within the configured deadline. One result per host, in target order;
+/// named hosts that are not registered are reported as not found.
+pub async fn send(
+    state: &Arc<AppState>,
+    created_by: &str,
+    resolved: &Resolved,
+    method: Method,
+    path: &str,
+    payload: Option<&Value>,
+) -> Vec<AuditResult> {
+    let started = Instant::now();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(state.config.fanout_deadline_secs);
+
+    let (progress, mut progress_rx) = mpsc::unbounded_channel();
+    let lanes = Lanes {
+        slots: Arc::new(Semaphore::new(state.config.fanout_concurrency.max(1))),
+        progress,
+    };
+    for agent in &resolved.agents {
+        let (state, lanes) = (state.clone(), lanes.clone());
+        let (created_by, agent, method) = (created_by.to_string(), agent.clone(), method.clone());
+        let (path, payload) = (path.to_string(), payload.cloned());
+        tokio::spawn(async move {
+            let result = send_one(&state, &created_by, &agent, method, &path, payload.as_ref(), &lanes).await;
+            let _ = lanes.progress.send(Progress::Done(result));
+        });
+    }
+    drop(lanes);
+
+    let mut queued: HashMap<String, String> = HashMap::new();
+    let mut done: HashMap<String, AuditResult> = HashMap::new();
+    while let Ok(Some(progress)) = tokio::time::timeout_at(deadline, progress_rx.recv()).await {
+        let result = match progress {
+            Progress::Queued(hostname, id) => {
+                queued.insert(hostname, id);
+                continue;
+            }
+            Progress::Done(result) => result,
+        };
+        match result.status.as_str() {
+            "ok" => tracing::info!("✅ {path} accepted by {}", result.hostname),
+            "unreachable" | "skipped_offline" => tracing::info!("⏳ {path} queued for {}", result.hostname),
+            _ => tracing::warn!("❌ {path} failed on {}: {}", result.hostname, result.detail),
+        }
+        done.insert(result.hostname.clone(), result);
+    }
+
+    let mut results: Vec<AuditResult> = resolved.missing.iter().map(|h| AuditResult::not_found(h)).collect();
+    results.extend(
+        resolved
+            .agents
+            .iter()
+            .map(|a| {
+                done.remove(&a.hostname).unwrap_or_else(|| match queued.get(&a.hostname) {
+                    Some(id) => AuditResult::timeout(&a.hostname).with_command(id),
+                    None => AuditResult::timeout(&a.hostname),
+                })
+            }),
+    );
+
+    let timed_out = results.iter().filter(|r| r.status == "timeout").count();
+    if timed_out > 0 {
+        tracing::warn!("⏱️ {path}: {timed_out} student(s) still pending at the deadline");
+    }
+    tracing::info!(
+        "📣 {path} sent to {} student(s) in {} ms",
+        resolved.agents.len(),
+        started.elapsed().as_millis()
+    );
+    results
+}
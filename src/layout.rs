@@ -0,0 +1,121 @@
+// ─────────────────────────────────────────────────────────────────
+//  layout.rs — Seating charts
+//
+//  Each classroom can have a grid layout stored in the
+//  {prefix}:layouts hash; the "default" entry covers the lab when no
+//  classroom is selected. Student lists and the screen grid are
+//  ordered front-to-back, left-to-right by seat, with machines that
+//  have no seat after them by hostname.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{RoomLayout, Seat};
+use crate::redis_store;
+use crate::state::AppState;
+
+/// Layout key used when no classroom is selected
+pub const DEFAULT_ROOM: &str = "default";
+
+/// Largest grid accepted in either direction
+const MAX_DIM: u32 = 50;
+
+impl RoomLayout {
+    /// Why this layout can't be saved, if anything is wrong with it
+    pub fn validate(&self) -> Option<String> {
+        if !(1..=MAX_DIM).contains(&self.rows) || !(1..=MAX_DIM).contains(&self.cols) {
+            return Some(format!("rows and cols must be between 1 and {MAX_DIM}."));
+        }
+        let inside = |s: &Seat| s.row < self.rows && s.col < self.cols;
+
+        let mut hosts = HashSet::new();
+        let mut taken = HashSet::new();
+        for a in &self.seats {
+            if a.hostname.trim().is_empty() {
+                return Some("Every seat needs a hostname.".to_string());
+            }
+            if !inside(&a.seat) {
+                return Some(format!("Seat for {} is outside the room.", a.hostname));
+            }
+            if !hosts.insert(a.hostname.as_str()) {
+                return Some(format!("{} is seated twice.", a.hostname));
+            }
+            if !taken.insert(a.seat) {
+                return Some(format!("Seat {},{} is used twice.", a.seat.row, a.seat.col));
+            }
+        }
+        match &self.teacher_desk {
+            Some(desk) if !inside(desk) => Some("Teacher desk is outside the room.".to_string()),
+            Some(desk) if taken.contains(desk) => Some("Teacher desk overlaps a seat.".to_string()),
+            _ => None,
+        }
+    }
+
+    /// hostname -> seat
+    pub fn seat_map(&self) -> HashMap<String, Seat> {
+        self.seats.iter().map(|a| (a.hostname.clone(), a.seat)).collect()
+    }
+}
+
+/// Layout for `classroom`, or the default one when none is selected
+pub async fn for_room(state: &AppState, classroom: Option<&str>) -> Option<RoomLayout> {
+    let mut conn = state.redis.clone();
+    redis_store::get_layout(&mut conn, &state.config.key_prefix, classroom.unwrap_or(DEFAULT_ROOM))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Sort key: seated machines by position, then the rest by hostname
+pub fn seat_order<'a>(seats: &HashMap<String, Seat>, hostname: &'a str) -> (bool, Option<Seat>, &'a str) {
+    let seat = seats.get(hostname).copied();
+    (seat.is_none(), seat, hostname)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    use crate::models::SeatAssignment;
+
+    fn layout(rows: u32, cols: u32, seats: &[(&str, u32, u32)], desk: Option<(u32, u32)>) -> RoomLayout {
+        RoomLayout {
+            room: DEFAULT_ROOM.to_string(),
+            rows,
+            cols,
+            teacher_desk: desk.map(|(row, col)| Seat { row, col }),
+            seats: seats
+                .iter()
+                .map(|(hostname, row, col)| SeatAssignment {
+                    hostname: hostname.to_string(),
+                    seat: Seat { row: *row, col: *col },
+                })
+                .collect(),
+            updated_by: String::new(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn accepts_a_valid_room() {
+        assert_eq!(layout(2, 3, &[("PC01", 0, 0), ("PC02", 1, 2)], Some((0, 2))).validate(), None);
+    }
+
+    #[test]
+    fn rejects_bad_rooms() {
+        let problems = [
+            layout(0, 3, &[], None),
+            layout(2, MAX_DIM + 1, &[], None),
+            layout(2, 3, &[(" ", 0, 0)], None),
+            layout(2, 3, &[("PC01", 2, 0)], None),
+            layout(2, 3, &[("PC01", 0, 0), ("PC01", 0, 1)], None),
+            layout(2, 3, &[("PC01", 0, 0), ("PC02", 0, 0)], None),
+            layout(2, 3, &[], Some((0, 3))),
+            layout(2, 3, &[("PC01", 1, 1)], Some((1, 1))),
+        ];
+        for layout in problems {
+            assert!(layout.validate().is_some(), "{}x{} accepted", layout.rows, layout.cols);
+        }
+    }
+}
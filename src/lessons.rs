@@ -0,0 +1,100 @@
+// ─────────────────────────────────────────────────────────────────
+//  lessons.rs — Tie ingested agent data to the running lesson
+//
+//  A lesson runs either for everyone (scope "all") or for one
+//  classroom. While it runs, everything posted to /api/agent/* is
+//  stamped with its id and also recorded under {prefix}:lesson:{id}:*
+//  so it can be queried after the lesson ends.
+// ─────────────────────────────────────────────────────────────────
+
+use crate::models::{AppList, AttendanceRecord, Heartbeat, LessonSession, Notification, Violation};
+use crate::redis_store;
+use crate::state::AppState;
+
+/// Scope of lessons that are not tied to a classroom
+pub const ALL_SCOPE: &str = "all";
+
+pub fn scope_of(lesson: &LessonSession) -> &str {
+    lesson.classroom.as_deref().unwrap_or(ALL_SCOPE)
+}
+
+/// Load running lessons into the cache on startup.
+pub async fn warm_cache(state: &AppState) {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let active = redis_store::get_active_lessons(&mut conn, prefix).await.unwrap_or_default();
+    for (scope, id) in active {
+        if let Ok(Some(lesson)) = redis_store::get_lesson(&mut conn, prefix, &id).await {
+            tracing::info!("📚 Lesson '{}' still running ({scope})", lesson.name);
+            state.active_lessons.insert(scope, lesson);
+        }
+    }
+}
+
+// Recording failures are logged and never fail the agent's request.
+
+pub async fn record_heartbeat(state: &AppState, lesson: &str, hb: &Heartbeat) {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let record = match redis_store::get_attendance(&mut conn, prefix, lesson, &hb.hostname).await {
+        Ok(Some(mut r)) => {
+            r.last_seen = hb.timestamp;
+            r.heartbeats += 1;
+            if !hb.username.is_empty() {
+                r.username = hb.username.clone();
+            }
+            r
+        }
+        _ => AttendanceRecord {
+            hostname: hb.hostname.clone(),
+            username: hb.username.clone(),
+            first_seen: hb.timestamp,
+            last_seen: hb.timestamp,
+            heartbeats: 1,
+        },
+    };
+    if let Err(e) = redis_store::store_attendance(&mut conn, prefix, lesson, &record).await {
+        tracing::warn!("Failed to record attendance for {}: {e}", hb.hostname);
+    }
+    count(state, lesson, &hb.hostname, "heartbeat").await;
+}
+
+pub async fn record_screenshot(state: &AppState, lesson: &str, hostname: &str) {
+    count(state, lesson, hostname, "screenshot").await;
+}
+
+pub async fn record_notification(state: &AppState, lesson: &str, n: &Notification) {
+    let mut conn = state.redis.clone();
+    if let Err(e) =
+        redis_store::add_lesson_notification(&mut conn, &state.config.key_prefix, lesson, n).await
+    {
+        tracing::warn!("Failed to record lesson notification for {}: {e}", n.hostname);
+    }
+    count(state, lesson, &n.hostname, "notification").await;
+}
+
+pub async fn record_apps(state: &AppState, lesson: &str, apps: &AppList) {
+    let mut conn = state.redis.clone();
+    if let Err(e) = redis_store::store_lesson_apps(&mut conn, &state.config.key_prefix, lesson, apps).await {
+        tracing::warn!("Failed to record lesson apps for {}: {e}", apps.hostname);
+    }
+    count(state, lesson, &apps.hostname, "apps").await;
+}
+
+pub async fn record_violation(state: &AppState, lesson: &str, v: &Violation) {
+    let mut conn = state.redis.clone();
+    if let Err(e) = redis_store::add_lesson_violation(&mut conn, &state.config.key_prefix, lesson, v).await {
+        tracing::warn!("Failed to record lesson violation for {}: {e}", v.hostname);
+    }
+    count(state, lesson, &v.hostname, "violation").await;
+}
+
+async fn count(state: &AppState, lesson: &str, hostname: &str, kind: &str) {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    if let Err(e) = redis_store::incr_lesson_activity(&mut conn, prefix, lesson, hostname, kind).await {
+        tracing::warn!("Failed to count lesson {kind} for {hostname}: {e}");
+    }
+}
@@ -0,0 +1,249 @@
+// ─────────────────────────────────────────────────────────────────
+//  lock_state.rs — Which machines are locked, and by whom
+//
+//  Kept in {prefix}:lock_states (hash, hostname → LockState) from the
+//  moment a lock is sent (or queued) until an unlock is. A queued lock
+//  is only pending: it is confirmed once the agent accepts the command
+//  and forgotten if the command fails or expires, so a lock that never
+//  arrived is not re-armed later. A machine that reports a boot time
+//  after a confirmed lock was last sent has lost it (reboot, agent
+//  restart with a fresh session), so the lock command is queued again
+//  and handed out with that same heartbeat.
+//
+//  Timed locks are also scheduled in {prefix}:lock_expiry (sorted set,
+//  hostname scored by release time); `expiry_task` unlocks them when
+//  due, even if the server restarted in between.
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::agent_client::AgentAddr;
+use crate::audit;
+use crate::commands;
+use crate::fanout;
+use crate::models::{AuditResult, Heartbeat, LockState};
+use crate::redis_store;
+use crate::state::AppState;
+use crate::targets::{self, Target};
+
+/// Allowance for the time a heartbeat spends in transit
+const BOOT_SLACK_SECS: i64 = 5;
+
+/// Hosts whose command went through or is still queued for them
+fn delivered_or_queued(results: &[AuditResult]) -> Vec<String> {
+    results
+        .iter()
+        .filter(|r| r.status == "ok" || queued_command(r).is_some())
+        .map(|r| r.hostname.clone())
+        .collect()
+}
+
+/// The command still on its way to the host, for results that are neither
+/// accepted nor refused yet
+pub fn queued_command(result: &AuditResult) -> Option<&str> {
+    match result.status.as_str() {
+        "unreachable" | "skipped_offline" | "timeout" => result.command_id.as_deref(),
+        _ => None,
+    }
+}
+
+/// Remember that `by` locked the hosts in `results` the command reached,
+/// until `expires_at` for a timed lock. Hosts it is still queued for are
+/// recorded as pending.
+pub async fn record_lock(
+    state: &AppState,
+    by: &str,
+    mode: &str,
+    expires_at: Option<DateTime<Utc>>,
+    results: &[AuditResult],
+) {
+    let hostnames = delivered_or_queued(results);
+    if hostnames.is_empty() {
+        return;
+    }
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    let now = Utc::now();
+    let mut pending = Vec::new();
+    for result in results.iter().filter(|r| hostnames.contains(&r.hostname)) {
+        let hostname = &result.hostname;
+        let pending_command = queued_command(result).map(str::to_string);
+        if pending_command.is_some() {
+            pending.push(hostname.clone());
+        }
+        let lock = LockState {
+            hostname: hostname.clone(),
+            mode: mode.to_string(),
+            locked_by: by.to_string(),
+            locked_at: now,
+            applied_at: now,
+            expires_at,
+            pending_command,
+        };
+        if let Err(e) = redis_store::store_lock_state(&mut conn, prefix, &lock).await {
+            tracing::warn!("Could not store lock state for {hostname}: {e}");
+        }
+        // A lock without a duration replaces any timed one
+        let scheduled = match expires_at {
+            Some(at) => redis_store::schedule_lock_expiry(&mut conn, prefix, hostname, at.timestamp()).await,
+            None => redis_store::cancel_lock_expiry(&mut conn, prefix, hostname).await,
+        };
+        if let Err(e) = scheduled {
+            tracing::warn!("Could not schedule lock release for {hostname}: {e}");
+        }
+    }
+    state.notify_teachers(&json!({
+        "type": "lock_state",
+        "mode": mode,
+        "by": by,
+        "expires_at": expires_at.map(|at| at.to_rfc3339()),
+        "hostnames": hostnames,
+        "pending": pending,
+    }));
+}
+
+/// Forget the locks on the hosts in `results` the unlock reached.
+pub async fn record_unlock(state: &AppState, by: &str, results: &[AuditResult]) {
+    release(state, by, delivered_or_queued(results), "unlocked").await;
+}
+
+/// Drop the lock state of `hostnames` and tell the dashboards why.
+async fn release(state: &AppState, by: &str, hostnames: Vec<String>, reason: &str) {
+    if hostnames.is_empty() {
+        return;
+    }
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    for hostname in &hostnames {
+        let cleared = redis_store::delete_lock_state(&mut conn, prefix, hostname).await.is_ok()
+            && redis_store::cancel_lock_expiry(&mut conn, prefix, hostname).await.is_ok();
+        if !cleared {
+            tracing::warn!("Could not clear lock state for {hostname}");
+        }
+    }
+    state.notify_teachers(&json!({
+        "type": "lock_state",
+        "mode": null,
+        "by": by,
+        "reason": reason,
+        "hostnames": hostnames,
+    }));
+}
+
+/// Background task: unlock machines whose timed lock has run out.
+/// The schedule lives in Redis, so locks due while the server was down
+/// are released as soon as it is back.
+pub async fn expiry_task(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+
+        let mut conn = state.redis.clone();
+        let prefix = &state.config.key_prefix;
+        let now = Utc::now();
+        let Ok(due) = redis_store::get_due_lock_expiries(&mut conn, prefix, now.timestamp()).await else {
+            continue;
+        };
+
+        // Group by the teacher who set each lock; skip stale entries
+        // (unlocked by hand, or re-locked since)
+        let mut by_teacher: HashMap<String, Vec<String>> = HashMap::new();
+        for hostname in due {
+            match redis_store::get_lock_state(&mut conn, prefix, &hostname).await {
+                Ok(Some(lock)) if lock.expires_at.is_some_and(|at| at <= now) => {
+                    by_teacher.entry(lock.locked_by).or_default().push(hostname);
+                }
+                Ok(_) => {
+                    let _ = redis_store::cancel_lock_expiry(&mut conn, prefix, &hostname).await;
+                }
+                Err(_) => {}
+            }
+        }
+
+        for (teacher, hostnames) in by_teacher {
+            tracing::info!("⏰ Timed lock by {teacher} ran out on {} student(s)", hostnames.len());
+            let target = Target { hostnames: Some(hostnames.clone()), ..Target::default() };
+            let results = match targets::resolve(&state, &target).await {
+                Ok(resolved) => fanout::send(&state, &teacher, &resolved, Method::POST, "/unlock", None).await,
+                Err(_) => continue,
+            };
+            // Released either way: a machine that refused the unlock must
+            // not be re-locked when it next restarts
+            release(&state, &teacher, hostnames.clone(), "expired").await;
+            audit::record_as(&state, &teacher, "auto_unlock", hostnames, json!({}), results).await;
+        }
+    }
+}
+
+/// Settle a pending lock once its command is done with: confirmed if the
+/// agent accepted it, forgotten if not. `None` while it is still on its way.
+async fn settle(state: &AppState, lock: &mut LockState) -> Option<bool> {
+    let id = lock.pending_command.as_deref()?;
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    match redis_store::get_command(&mut conn, prefix, id).await {
+        Ok(Some(cmd)) if cmd.status == "acked" => {
+            lock.applied_at = cmd.acked_at.unwrap_or_else(Utc::now);
+            lock.pending_command = None;
+            Some(true)
+        }
+        Ok(Some(cmd)) if !cmd.is_finished() => None,
+        // Failed, expired, or pruned without an answer
+        Ok(_) => Some(false),
+        Err(_) => None,
+    }
+}
+
+/// Settle a pending lock, and re-send a confirmed one to a locked machine
+/// that has booted since it was sent. Run on every heartbeat, before
+/// queued commands are handed out.
+pub async fn reconcile(state: &AppState, hb: &Heartbeat) {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    let Ok(Some(mut lock)) = redis_store::get_lock_state(&mut conn, prefix, &hb.hostname).await else {
+        return;
+    };
+    if lock.pending_command.is_some() {
+        match settle(state, &mut lock).await {
+            Some(true) => {
+                if let Err(e) = redis_store::store_lock_state(&mut conn, prefix, &lock).await {
+                    tracing::warn!("Could not store lock state for {}: {e}", hb.hostname);
+                }
+            }
+            Some(false) => {
+                tracing::info!("🔓 {} lock never reached {}; forgetting it", lock.mode, hb.hostname);
+                release(state, &lock.locked_by, vec![hb.hostname.clone()], "undelivered").await;
+            }
+            None => {}
+        }
+        return;
+    }
+
+    let uptime = chrono::Duration::try_seconds(i64::try_from(hb.uptime_secs).unwrap_or(i64::MAX));
+    let Some(booted_at) = uptime.and_then(|up| hb.timestamp.checked_sub_signed(up)) else {
+        return;
+    };
+    if booted_at <= lock.applied_at + chrono::Duration::seconds(BOOT_SLACK_SECS) {
+        return;
+    }
+
+    let agent = AgentAddr {
+        hostname: hb.hostname.clone(),
+        ip: hb.ip.clone(),
+        port: hb.port.to_string(),
+    };
+    let path = format!("/lock/{}", lock.mode);
+    let cmd = commands::enqueue(state, &lock.locked_by, &agent, Method::POST, &path, None).await;
+    tracing::info!("🔁 {} restarted while locked; re-sending {} lock ({})", hb.hostname, lock.mode, cmd.id);
+
+    lock.applied_at = hb.timestamp;
+    lock.pending_command = Some(cmd.id);
+    if let Err(e) = redis_store::store_lock_state(&mut conn, prefix, &lock).await {
+        tracing::warn!("Could not store lock state for {}: {e}", hb.hostname);
+    }
+}
@@ -1,17 +1,42 @@
-use axum::routing::{get, post};
+use axum::extract::DefaultBodyLimit;
+use axum::middleware;
+use axum::routing::{delete, get, post, put};
 use axum::Router;
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::Json;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
 
+mod agent_auth;
+mod agent_channel;
+mod agent_client;
+mod audit;
+mod auth;
+mod commands;
 mod config;
+mod conflicts;
+mod csv;
+mod exam;
+mod fanout;
+mod layout;
+mod lessons;
+mod lock_state;
 mod models;
+mod overrides;
+mod permissions;
+mod policy;
+mod rate_limit;
 mod redis_store;
+mod roster;
 mod routes;
+mod schedule;
+mod security;
+mod server_key;
 mod state;
+mod targets;
+mod tls;
 
 use state::AppState;
 
@@ -32,7 +57,19 @@ async fn main() {
         .await
         .expect("Cannot connect to Redis — is it running?");
 
-    let shared = Arc::new(AppState::new(cfg.clone(), redis_conn));
+    let signing_key = server_key::load_or_generate(&cfg.signing_key_path)
+        .unwrap_or_else(|e| panic!("Signing key setup failed: {e}"));
+    let shared = Arc::new(AppState::new(cfg.clone(), redis_conn, signing_key));
+
+    let tls = if cfg.tls_enabled {
+        let setup = tls::load_or_generate(&cfg)
+            .await
+            .unwrap_or_else(|e| panic!("TLS setup failed: {e}"));
+        tracing::info!("🔐 TLS certificate fingerprint (SHA-256): {}", setup.fingerprint);
+        Some(setup)
+    } else {
+        None
+    };
 
     // Publish teacher address to Redis so students can discover us
     {
@@ -45,84 +82,252 @@ async fn main() {
             ).await;
             tracing::info!("Teacher address published to Redis: {ip}:{}", shared.config.port);
         }
+
+        // Public key next to the address, so agents can verify our commands
+        let key = format!("{prefix}:server:pubkey");
+        let _: Result<(), _> = redis::AsyncCommands::set::<_, _, ()>(
+            &mut conn, &key, server_key::public_key_hex(&shared.signing_key),
+        ).await;
+        tracing::info!("Command signing key published to Redis");
+
+        // Certificate fingerprint for agents to pin; absent means plain HTTP
+        let key = format!("{prefix}:server:cert_fingerprint");
+        let _: Result<(), _> = match &tls {
+            Some(setup) => redis::AsyncCommands::set::<_, _, ()>(
+                &mut conn, &key, &setup.fingerprint,
+            ).await,
+            None => redis::AsyncCommands::del::<_, ()>(&mut conn, &key).await,
+        };
     }
 
-    // Push initial ban config to Redis so students can pick it up
+    // Push initial ban config and SAU mode to Redis so students can pick them up
     {
         let mut conn = shared.redis.clone();
         let prefix = &shared.config.key_prefix;
-        let ban_json = serde_json::json!({
-            "banned_processes": shared.config.banned_apps,
-            "banned_domains": shared.config.banned_sites,
-        });
-        let key = format!("{prefix}:ban_config");
-        let _: Result<(), _> = redis::AsyncCommands::set::<_, _, ()>(
-            &mut conn, &key, ban_json.to_string(),
-        ).await;
-        tracing::info!("Ban config published to Redis");
+        let global = shared.effective_policy();
+        match policy::publish(&mut conn, prefix, None, &global).await {
+            Ok(()) => tracing::info!("Ban config published to Redis (SAU mode: {})", global.sau_mode),
+            Err(e) => tracing::warn!("Could not publish ban config: {e}"),
+        }
 
-        // Push initial SAU mode
-        let sau_key = format!("{prefix}:sau_mode");
-        let _: Result<(), _> = redis::AsyncCommands::set::<_, _, ()>(
-            &mut conn, &sau_key, if shared.config.sau_mode { "1" } else { "0" },
-        ).await;
-        tracing::info!("SAU mode published to Redis: {}", shared.config.sau_mode);
+        // Republish every classroom's policy and warm the room cache
+        for room in redis_store::get_all_classrooms(&mut conn, prefix).await.unwrap_or_default() {
+            let _ = policy::publish(&mut conn, prefix, Some(&room.id), &room.policy).await;
+            let members = redis_store::get_classroom_members(&mut conn, prefix, &room.id)
+                .await
+                .unwrap_or_default();
+            for hostname in members {
+                shared.student_rooms.insert(hostname, room.id.clone());
+            }
+        }
     }
 
+    lessons::warm_cache(&shared).await;
+    roster::warm_cache(&shared).await;
+    auth::ensure_bootstrap_admin(&shared).await;
+
     // Background tasks
     tokio::spawn(ip_update_task(shared.clone()));
+    tokio::spawn(schedule::schedule_task(shared.clone()));
+    tokio::spawn(overrides::sweep_task(shared.clone()));
+    tokio::spawn(commands::retry_task(shared.clone()));
+    tokio::spawn(lock_state::expiry_task(shared.clone()));
 
     // Routes
-    let api = Router::new()
-        // Teacher-facing reads
+    let public_api = Router::new()
         .route("/health", get(routes::health::health))
+        .route("/auth/login", post(routes::auth::login))
+        .route("/auth/logout", post(routes::auth::logout));
+
+    let teacher_api = Router::new()
+        // Teacher-facing reads
+        .route("/auth/me", get(routes::auth::me))
         .route("/info", get(routes::info::info))
+        .route("/presence", get(routes::presence::presence))
         .route("/violations", get(routes::violations::violations))
+        .route("/violations/export", get(routes::roster::export_violations))
         .route("/config", get(routes::config_route::get_config))
         .route("/students", get(routes::students::list_students))
         .route("/students/active", get(routes::students::list_active))
+        .route("/students/lock", post(routes::lock::lock_targets))
+        .route("/students/unlock", post(routes::lock::unlock_targets))
+        .route("/students/open-url", post(routes::lock::open_url_targets))
         .route("/students/:hostname", get(routes::students::student_detail))
         .route("/students/:hostname/lock", post(routes::lock::lock_student))
+        .route("/students/:hostname/unlock", post(routes::lock::unlock_student))
         .route("/students/:hostname/open-url", post(routes::lock::open_url_student))
         .route("/apps/:hostname", get(routes::lock::get_apps_student))
         .route("/broadcast/open-url", post(routes::lock::broadcast_open_url))
-        .route("/config", axum::routing::put(routes::config_route::update_config))
+        .route("/broadcast/lock", post(routes::lock::broadcast_lock))
+        .route("/broadcast/unlock", post(routes::lock::broadcast_unlock))
+        .route("/config", put(routes::config_route::update_config))
+        .route("/schedule", get(routes::config_route::get_schedule))
+        // Teacher accounts
+        .route("/teachers", get(routes::auth::list_teachers).post(routes::auth::create_teacher))
+        .route("/teachers/:username", delete(routes::auth::delete_teacher))
+        .route("/teachers/:username/password", put(routes::auth::change_password))
+        .route("/teachers/:username/role", put(routes::auth::change_role))
+        // Audit trail
+        .route("/audit", get(routes::audit::audit_log))
+        // Agent enrollment
+        .route("/agents/enroll", post(routes::enrollment::enroll_agent))
+        .route("/agents/enrolled", get(routes::enrollment::list_enrolled))
+        .route("/agents/:hostname/enrollment", delete(routes::enrollment::revoke_agent))
+        .route("/agents/throttle", get(routes::throttle::throttle_stats))
+        // Student groups
+        .route("/groups", get(routes::groups::list_groups).post(routes::groups::create_group))
+        .route(
+            "/groups/:name",
+            get(routes::groups::get_group).delete(routes::groups::delete_group),
+        )
+        .route(
+            "/groups/:name/members",
+            put(routes::groups::set_members).post(routes::groups::add_members),
+        )
+        .route("/groups/:name/members/:hostname", delete(routes::groups::remove_member))
+        .route("/groups/:name/lock", post(routes::lock::lock_group))
+        .route("/groups/:name/unlock", post(routes::lock::unlock_group))
+        .route("/groups/:name/open-url", post(routes::lock::open_url_group))
+        // Per-student policy overrides
+        .route("/access-requests", get(routes::access_requests::list))
+        .route("/access-requests/:id/approve", post(routes::access_requests::approve))
+        .route("/access-requests/:id/deny", post(routes::access_requests::deny))
+        .route(
+            "/overrides",
+            get(routes::overrides::list_overrides).post(routes::overrides::create_override),
+        )
+        .route("/overrides/:id", delete(routes::overrides::delete_override))
+        // Queued agent commands
+        .route("/commands", get(routes::commands::list_commands))
+        .route("/commands/:id", get(routes::commands::get_command))
+        // Roster
+        .route("/roster", get(routes::roster::list_roster).post(routes::roster::upsert_student))
+        .route("/roster/import", post(routes::roster::import_roster))
+        .route("/roster/:id", delete(routes::roster::delete_student))
+        .route("/roster/:id/assignment", put(routes::roster::assign_student))
+        // Seating charts
+        .route("/layouts", get(routes::layouts::list_layouts))
+        .route(
+            "/layouts/:room",
+            get(routes::layouts::get_layout)
+                .put(routes::layouts::put_layout)
+                .delete(routes::layouts::delete_layout),
+        )
+        // Lesson sessions
+        .route("/sessions", get(routes::sessions::list_lessons))
+        .route("/sessions/start", post(routes::sessions::start_lesson))
+        .route("/sessions/end", post(routes::sessions::end_lesson))
+        .route("/sessions/active", get(routes::sessions::active_lessons))
+        .route("/sessions/:id", get(routes::sessions::get_lesson))
+        .route("/sessions/:id/violations", get(routes::sessions::lesson_violations))
+        .route("/sessions/:id/attendance", get(routes::sessions::lesson_attendance))
+        .route("/sessions/:id/activity", get(routes::sessions::lesson_activity))
+        // Classrooms
+        .route(
+            "/classrooms",
+            get(routes::classrooms::list_classrooms).post(routes::classrooms::create_classroom),
+        )
+        .route(
+            "/classrooms/:id",
+            get(routes::classrooms::get_classroom)
+                .put(routes::classrooms::update_classroom)
+                .delete(routes::classrooms::delete_classroom),
+        )
+        .route(
+            "/classrooms/:id/students",
+            get(routes::classrooms::classroom_students).put(routes::classrooms::assign_students),
+        )
+        .route(
+            "/classrooms/:id/students/:hostname",
+            delete(routes::classrooms::unassign_student),
+        )
+        .route("/classrooms/:id/violations", get(routes::classrooms::classroom_violations))
+        // Screen streaming info
+        .route("/screen/students", get(screen_students_handler));
+
+    let agent_api = Router::new()
         // Agent data ingestion
         .route("/agent/heartbeat", post(routes::agent::heartbeat))
         .route("/agent/screenshot", post(routes::agent::screenshot))
         .route("/agent/notification", post(routes::agent::notification))
         .route("/agent/apps", post(routes::agent::apps))
         .route("/agent/violation", post(routes::agent::violation))
-        // Screen streaming info
-        .route("/screen/students", get(screen_students_handler));
+        .route("/agent/access-request", post(routes::access_requests::submit))
+        .route("/agent/command-ack", post(routes::commands::command_ack))
+        .route_layer(middleware::from_fn_with_state(shared.clone(), agent_auth::require_agent))
+        // Body caps are enforced per endpoint by require_agent
+        .layer(DefaultBodyLimit::disable());
 
-    let app = Router::new()
-        .nest("/api", api)
+    // Every route is checked against the permission table
+    let api = public_api
+        .merge(teacher_api)
+        .merge(agent_api)
+        .route_layer(middleware::from_fn_with_state(shared.clone(), auth::authorize));
+
+    let ws = Router::new()
         .route("/ws", get(routes::ws::ws_handler))
-        .route("/ws/screen", get(routes::screen_ws::ws_screen_student))
+        .route(
+            "/ws/screen",
+            get(routes::screen_ws::ws_screen_student).route_layer(
+                middleware::from_fn_with_state(shared.clone(), agent_auth::require_agent),
+            ),
+        )
         .route("/ws/screen/view", get(routes::screen_ws::ws_screen_teacher))
+        .route(
+            "/ws/agent",
+            get(agent_channel::ws_agent).route_layer(middleware::from_fn_with_state(
+                shared.clone(),
+                agent_auth::require_agent,
+            )),
+        )
+        .route_layer(middleware::from_fn_with_state(shared.clone(), auth::authorize));
+
+    let app = Router::new()
+        .nest("/api", api)
+        .merge(ws)
         .fallback_service(ServeDir::new("frontend"))
-        .layer(CorsLayer::permissive())
+        .layer(middleware::from_fn_with_state(shared.clone(), security::security_headers))
+        .layer(security::cors_layer(&cfg))
         .with_state(shared);
 
     let addr = format!("0.0.0.0:{}", cfg.port);
-    let listener = tokio::net::TcpListener::bind(&addr)
-        .await
-        .expect("Cannot bind address");
 
-    tracing::info!("🚀 NiShack backend listening on http://{addr}");
-    axum::serve(listener, app).await.unwrap();
+    if let Some(setup) = tls {
+        let socket_addr = addr.parse().expect("Invalid listen address");
+        tracing::info!("🚀 NiShack backend listening on https://{addr}");
+        axum_server::bind_rustls(socket_addr, setup.rustls)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .unwrap();
+    } else {
+        let listener = tokio::net::TcpListener::bind(&addr)
+            .await
+            .expect("Cannot bind address");
+
+        tracing::info!("🚀 NiShack backend listening on http://{addr}");
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .unwrap();
+    }
 }
 
-/// GET /api/screen/students — list students currently streaming screens
+/// GET /api/screen/students?classroom=<id> — list students currently streaming
+/// screens, in seating-chart order
 async fn screen_students_handler(
     State(state): State<Arc<AppState>>,
+    Query(q): Query<routes::students::StudentQuery>,
 ) -> Json<serde_json::Value> {
-    let students: Vec<String> = state
+    let mut students: Vec<String> = state
         .screen_latest
         .iter()
         .map(|e| e.key().clone())
+        .filter(|h| state.in_classroom(h, q.classroom.as_deref()))
         .collect();
+    let seats = layout::for_room(&state, q.classroom.as_deref())
+        .await
+        .map(|l| l.seat_map())
+        .unwrap_or_default();
+    students.sort_by(|a, b| layout::seat_order(&seats, a).cmp(&layout::seat_order(&seats, b)));
     Json(serde_json::json!({
         "count": students.len(),
         "students": students,
@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 // ── Agent heartbeat ──────────────────────────────────────
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +14,9 @@ pub struct Heartbeat {
     pub ram_usage: f32,
     pub uptime_secs: u64,
     pub timestamp: DateTime<Utc>,
+    /// Lesson running when this was received (stamped by the server)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
 }
 
 // ── Screenshot from student ──────────────────────────────
@@ -21,6 +25,8 @@ pub struct Screenshot {
     pub hostname: String,
     pub image_url: String, // base64 data-uri or URL
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
 }
 
 // ── Notification from student ────────────────────────────
@@ -31,6 +37,8 @@ pub struct Notification {
     pub message: String,
     pub level: String, // info | warning | error
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
 }
 
 // ── Running applications ─────────────────────────────────
@@ -54,6 +62,8 @@ pub struct AppList {
     pub applications: Vec<Application>,
     pub browser_tabs: Vec<BrowserTab>,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
 }
 
 // ── Violation ────────────────────────────────────────────
@@ -64,6 +74,175 @@ pub struct Violation {
     pub detail: String,
     pub severity: String, // low | medium | high
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Roster student on the machine at the time (stamped by the server)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub student_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub student_name: Option<String>,
+}
+
+// ── Ban policy (global or per classroom) ─────────────────
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BanPolicy {
+    pub banned_sites: Vec<String>,
+    pub banned_apps: Vec<String>,
+    #[serde(default)]
+    pub sau_mode: bool,
+    /// Allowlist-only: anything outside `allowed_*` is a violation
+    #[serde(default)]
+    pub exam_mode: bool,
+    #[serde(default)]
+    pub allowed_sites: Vec<String>,
+    #[serde(default)]
+    pub allowed_apps: Vec<String>,
+}
+
+// ── Per-student policy override ──────────────────────────
+/// Adjusts the ban lists for one machine or one logged-in user.
+/// `allow_*` entries are lifted from the ban lists (and added to the
+/// exam allowlist); `ban_*` entries are added on top.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyOverride {
+    pub id: String,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub allow_sites: Vec<String>,
+    #[serde(default)]
+    pub allow_apps: Vec<String>,
+    #[serde(default)]
+    pub ban_sites: Vec<String>,
+    #[serde(default)]
+    pub ban_apps: Vec<String>,
+    #[serde(default)]
+    pub reason: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// ── Student access request ───────────────────────────────
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessRequest {
+    #[serde(default)]
+    pub id: String,
+    pub hostname: String,
+    #[serde(default)]
+    pub username: String,
+    pub kind: String, // site | app
+    /// Domain or app name the student wants unblocked
+    pub target: String,
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub status: String, // pending | approved | denied
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub decided_by: Option<String>,
+    #[serde(default)]
+    pub decided_at: Option<DateTime<Utc>>,
+    /// Note shown to the student with the decision
+    #[serde(default)]
+    pub decision_note: String,
+    /// Exception created on approval
+    #[serde(default)]
+    pub override_id: Option<String>,
+}
+
+// ── Roster student ───────────────────────────────────────
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RosterStudent {
+    /// School student number
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub email: String,
+    /// Personal OS login; identifies the student on any machine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Machine the student is seated at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+}
+
+// ── Classroom ────────────────────────────────────────────
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Classroom {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub policy: BanPolicy,
+    pub created_at: DateTime<Utc>,
+}
+
+// ── Room layout (seating chart) ──────────────────────────
+/// Grid position, counted from 0 at the front-left of the room
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Seat {
+    pub row: u32,
+    pub col: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatAssignment {
+    pub hostname: String,
+    #[serde(flatten)]
+    pub seat: Seat,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomLayout {
+    /// Classroom id, or "default" for the lab as a whole
+    #[serde(default)]
+    pub room: String,
+    pub rows: u32,
+    pub cols: u32,
+    #[serde(default)]
+    pub teacher_desk: Option<Seat>,
+    #[serde(default)]
+    pub seats: Vec<SeatAssignment>,
+    #[serde(default)]
+    pub updated_by: String,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+// ── Student group ────────────────────────────────────────
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StudentGroup {
+    pub name: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// ── Lesson session ───────────────────────────────────────
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LessonSession {
+    pub id: String,
+    pub name: String,
+    /// Classroom the lesson is for; `None` covers every student
+    #[serde(default)]
+    pub classroom: Option<String>,
+    pub started_by: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(default)]
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+/// A student's presence during one lesson
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttendanceRecord {
+    pub hostname: String,
+    pub username: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub heartbeats: u64,
 }
 
 // ── Student summary (returned by /api/students) ──────────
@@ -79,6 +258,37 @@ pub struct StudentSummary {
     pub ram_usage: f32,
     pub violation_count: i64,
     pub last_seen: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub classroom: Option<String>,
+    /// Roster entry mapped to this machine or its login
+    #[serde(default)]
+    pub student: Option<RosterStudent>,
+    /// Position in the room layout the list was ordered by
+    #[serde(default)]
+    pub seat: Option<Seat>,
+    /// Set while a teacher has the machine locked
+    #[serde(default)]
+    pub lock: Option<LockState>,
+}
+
+// ── Lock state ───────────────────────────────────────────
+/// A lock a teacher put on a machine and hasn't released yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockState {
+    pub hostname: String,
+    /// "soft" or "hard"
+    pub mode: String,
+    pub locked_by: String,
+    pub locked_at: DateTime<Utc>,
+    /// When the lock command was last sent; re-sent if the machine boots after this
+    pub applied_at: DateTime<Utc>,
+    /// Timed locks are released by the server at this time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Lock command the agent hasn't accepted yet; the lock only counts
+    /// once it has, and is dropped if the command fails or expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_command: Option<String>,
 }
 
 // ── Full student detail ──────────────────────────────────
@@ -89,6 +299,9 @@ pub struct StudentDetail {
     pub apps: Option<AppList>,
     pub notifications: Vec<Notification>,
     pub violations: Vec<Violation>,
+    /// Overrides currently applying to this machine or its user
+    #[serde(default)]
+    pub overrides: Vec<PolicyOverride>,
 }
 
 // ── WebSocket chat message ───────────────────────────────
@@ -110,3 +323,141 @@ pub struct ChatMessage {
     #[serde(default)]
     pub timestamp: Option<DateTime<Utc>>,
 }
+
+// ── Teacher account ──────────────────────────────────────
+/// Ordered from least to most privileged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Teaching assistant: watches screens and the student list
+    Observer,
+    /// Locks machines and opens URLs
+    #[default]
+    Teacher,
+    /// IT admin: ban lists, accounts and agent enrollment
+    Admin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeacherAccount {
+    pub username: String,
+    pub display_name: String,
+    pub password_hash: String,
+    #[serde(default)]
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+// ── Agent enrollment ─────────────────────────────────────
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentEnrollment {
+    pub hostname: String,
+    pub secret: String,
+    pub enrolled_by: String,
+    pub enrolled_at: DateTime<Utc>,
+}
+
+// ── Queued agent command ─────────────────────────────────
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentCommand {
+    pub id: String,
+    pub hostname: String,
+    pub method: String,
+    /// Agent endpoint, e.g. "/lock/soft"
+    pub path: String,
+    #[serde(default)]
+    pub body: Option<Value>,
+    pub status: String, // pending | sending | delivered | acked | failed | expired
+    #[serde(default)]
+    pub attempts: u32,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    /// Given up on if not delivered by then
+    pub expires_at: DateTime<Utc>,
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub delivered_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub acked_at: Option<DateTime<Utc>>,
+    /// What the agent answered
+    #[serde(default)]
+    pub result: Option<Value>,
+}
+
+// ── Audit trail ──────────────────────────────────────────
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditResult {
+    pub hostname: String,
+    pub status: String, // ok | error | not_found | unreachable | skipped_offline | timeout
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+    /// What the agent answered when it refused the command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    /// Queued command carrying this result (see /api/commands/:id)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_id: Option<String>,
+}
+
+impl AuditResult {
+    fn new(hostname: &str, status: &str, detail: &str) -> Self {
+        Self {
+            hostname: hostname.to_string(),
+            status: status.to_string(),
+            detail: detail.to_string(),
+            http_status: None,
+            command_id: None,
+        }
+    }
+
+    pub fn ok(hostname: &str) -> Self {
+        Self::new(hostname, "ok", "")
+    }
+
+    pub fn error(hostname: &str, detail: &str) -> Self {
+        Self::new(hostname, "error", detail)
+    }
+
+    pub fn not_found(hostname: &str) -> Self {
+        Self::new(hostname, "not_found", "")
+    }
+
+    /// Reached the agent, which refused it
+    pub fn http_error(hostname: &str, status: u16) -> Self {
+        let mut result = Self::new(hostname, "error", &format!("Student returned {status}"));
+        result.http_status = Some(status);
+        result
+    }
+
+    /// Tried and not delivered; the queued command is retried
+    pub fn unreachable(hostname: &str, detail: &str) -> Self {
+        Self::new(hostname, "unreachable", detail)
+    }
+
+    /// Not tried because the student is offline; the queued command waits for it
+    pub fn skipped_offline(hostname: &str) -> Self {
+        Self::new(hostname, "skipped_offline", "")
+    }
+
+    /// Still in flight when the deadline passed; the queued command is retried
+    pub fn timeout(hostname: &str) -> Self {
+        Self::new(hostname, "timeout", "")
+    }
+
+    pub fn with_command(mut self, id: &str) -> Self {
+        self.command_id = Some(id.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub actor: String,
+    pub action: String,
+    pub targets: Vec<String>,
+    pub params: Value,
+    pub results: Vec<AuditResult>,
+    pub timestamp: DateTime<Utc>,
+}
@@ -0,0 +1,200 @@
+// ─────────────────────────────────────────────────────────────────
+//  overrides.rs — Per-student exceptions to the ban lists
+//
+//  Entries live in the {prefix}:overrides hash. For agents, all live
+//  entries for one subject are merged and published to
+//    {prefix}:policy_override:host:{hostname}
+//    {prefix}:policy_override:user:{username}
+//  as { allow_processes, allow_domains, banned_processes,
+//  banned_domains, expires_at }. Agents apply them on top of
+//  ban_config: add the banned_* entries, then drop the allow_* ones.
+//  Expired entries are swept and republished every 30 seconds.
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::models::{BanPolicy, PolicyOverride};
+use crate::redis_store;
+use crate::state::AppState;
+
+impl PolicyOverride {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|t| t <= now)
+    }
+
+    /// Published key suffix this entry belongs to
+    pub fn subject(&self) -> Option<String> {
+        match (&self.hostname, &self.username) {
+            (Some(h), _) => Some(format!("host:{h}")),
+            (None, Some(u)) => Some(format!("user:{u}")),
+            (None, None) => None,
+        }
+    }
+
+    pub fn applies_to(&self, hostname: &str, username: &str) -> bool {
+        self.hostname.as_deref() == Some(hostname)
+            || (!username.is_empty() && self.username.as_deref() == Some(username))
+    }
+}
+
+impl BanPolicy {
+    /// These lists with `overrides` applied
+    pub fn with_overrides(&self, overrides: &[PolicyOverride]) -> BanPolicy {
+        let mut policy = self.clone();
+        for o in overrides {
+            policy.banned_sites.extend(o.ban_sites.iter().cloned());
+            policy.banned_apps.extend(o.ban_apps.iter().cloned());
+            policy.allowed_sites.extend(o.allow_sites.iter().cloned());
+            policy.allowed_apps.extend(o.allow_apps.iter().cloned());
+        }
+        for o in overrides {
+            policy.banned_sites.retain(|s| !o.allow_sites.iter().any(|a| a.eq_ignore_ascii_case(s)));
+            policy.banned_apps.retain(|s| !o.allow_apps.iter().any(|a| a.eq_ignore_ascii_case(s)));
+        }
+        policy
+    }
+}
+
+/// Live overrides for a machine and whoever is logged in on it
+pub async fn active_for(
+    state: &AppState,
+    conn: &mut ConnectionManager,
+    hostname: &str,
+    username: &str,
+) -> Vec<PolicyOverride> {
+    let now = Utc::now();
+    let mut list: Vec<PolicyOverride> = redis_store::get_all_overrides(conn, &state.config.key_prefix)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|o| !o.is_expired(now) && o.applies_to(hostname, username))
+        .collect();
+    list.sort_by_key(|o| o.created_at);
+    list
+}
+
+/// Recompute and publish the merged overrides for one subject.
+pub async fn publish_subject(state: &AppState, conn: &mut ConnectionManager, subject: &str) {
+    let prefix = &state.config.key_prefix;
+    let now = Utc::now();
+    let entries: Vec<PolicyOverride> = redis_store::get_all_overrides(conn, prefix)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|o| !o.is_expired(now) && o.subject().as_deref() == Some(subject))
+        .collect();
+
+    let json = if entries.is_empty() {
+        None
+    } else {
+        let collect = |f: fn(&PolicyOverride) -> &Vec<String>| -> BTreeSet<String> {
+            entries.iter().flat_map(|o| f(o).iter().cloned()).collect()
+        };
+        Some(
+            serde_json::json!({
+                "allow_processes": collect(|o| &o.allow_apps),
+                "allow_domains": collect(|o| &o.allow_sites),
+                "banned_processes": collect(|o| &o.ban_apps),
+                "banned_domains": collect(|o| &o.ban_sites),
+                "expires_at": entries.iter().filter_map(|o| o.expires_at).min(),
+            })
+            .to_string(),
+        )
+    };
+
+    if let Err(e) = redis_store::set_published_override(conn, prefix, subject, json.as_deref()).await {
+        tracing::warn!("Could not publish overrides for {subject}: {e}");
+    }
+}
+
+/// Store a new override and publish it for its subject.
+pub async fn create(state: &AppState, entry: &PolicyOverride) -> redis::RedisResult<()> {
+    let mut conn = state.redis.clone();
+    redis_store::store_override(&mut conn, &state.config.key_prefix, entry).await?;
+    if let Some(subject) = entry.subject() {
+        publish_subject(state, &mut conn, &subject).await;
+    }
+    Ok(())
+}
+
+/// Background task: drop expired overrides and republish their subjects.
+pub async fn sweep_task(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(30));
+    loop {
+        interval.tick().await;
+
+        let mut conn = state.redis.clone();
+        let prefix = &state.config.key_prefix;
+        let now = Utc::now();
+        let Ok(all) = redis_store::get_all_overrides(&mut conn, prefix).await else { continue };
+
+        let mut touched = BTreeSet::new();
+        for o in all.iter().filter(|o| o.is_expired(now)) {
+            if redis_store::delete_override(&mut conn, prefix, &o.id).await.is_ok() {
+                tracing::info!("⏱️ Override {} expired", o.id);
+                touched.extend(o.subject());
+            }
+        }
+        for subject in touched {
+            publish_subject(&state, &mut conn, &subject).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(allow_sites: &[&str], ban_apps: &[&str]) -> PolicyOverride {
+        PolicyOverride {
+            id: "o1".into(),
+            hostname: Some("LAB2-PC07".into()),
+            username: None,
+            allow_sites: allow_sites.iter().map(|s| s.to_string()).collect(),
+            allow_apps: vec![],
+            ban_sites: vec![],
+            ban_apps: ban_apps.iter().map(|s| s.to_string()).collect(),
+            reason: String::new(),
+            created_by: "teacher".into(),
+            created_at: Utc::now(),
+            expires_at: None,
+        }
+    }
+
+    fn base() -> BanPolicy {
+        BanPolicy {
+            banned_sites: vec!["youtube.com".into(), "tiktok.com".into()],
+            banned_apps: vec!["Steam".into()],
+            ..BanPolicy::default()
+        }
+    }
+
+    #[test]
+    fn no_overrides_leave_the_policy_alone() {
+        let merged = base().with_overrides(&[]);
+        assert_eq!(merged.banned_sites, base().banned_sites);
+        assert_eq!(merged.banned_apps, base().banned_apps);
+    }
+
+    #[test]
+    fn allows_lift_bans_and_join_the_allowlist() {
+        let merged = base().with_overrides(&[entry(&["YouTube.com"], &[])]);
+        assert_eq!(merged.banned_sites, vec!["tiktok.com"]);
+        assert_eq!(merged.allowed_sites, vec!["YouTube.com"]);
+    }
+
+    #[test]
+    fn bans_add_up_and_an_allow_in_any_override_wins() {
+        let merged = base().with_overrides(&[entry(&[], &["Discord"]), entry(&[], &["Roblox"])]);
+        assert_eq!(merged.banned_apps, vec!["Steam", "Discord", "Roblox"]);
+
+        let mut allow = entry(&[], &[]);
+        allow.allow_apps = vec!["discord".into()];
+        let merged = base().with_overrides(&[entry(&[], &["Discord"]), allow]);
+        assert_eq!(merged.banned_apps, vec!["Steam"]);
+    }
+}
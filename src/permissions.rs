@@ -0,0 +1,199 @@
+// ─────────────────────────────────────────────────────────────────
+//  permissions.rs — Who may call what
+//
+//  One entry per handler registered in main.rs. Routes missing from
+//  the table are refused, so new routes must be added here.
+// ─────────────────────────────────────────────────────────────────
+
+use axum::http::Method;
+
+use crate::models::Role;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// No authentication
+    Public,
+    /// Signed by an enrolled agent (checked by agent_auth)
+    Agent,
+    /// Teacher session with at least this role
+    Role(Role),
+    /// Either of the above (chat socket shared by teachers and students)
+    RoleOrAgent(Role),
+}
+
+use Access::{Agent, Public};
+const OBSERVER: Access = Access::Role(Role::Observer);
+const TEACHER: Access = Access::Role(Role::Teacher);
+const ADMIN: Access = Access::Role(Role::Admin);
+
+/// (method, route pattern as registered, required access)
+pub const PERMISSIONS: &[(&str, &str, Access)] = &[
+    // Public
+    ("GET", "/api/health", Public),
+    ("POST", "/api/auth/login", Public),
+    ("POST", "/api/auth/logout", Public),
+    // Observers: watch screens, read the student list
+    ("GET", "/api/auth/me", OBSERVER),
+    ("GET", "/api/info", OBSERVER),
+    ("GET", "/api/presence", OBSERVER),
+    ("GET", "/api/students", OBSERVER),
+    ("GET", "/api/students/active", OBSERVER),
+    ("GET", "/api/students/:hostname", OBSERVER),
+    ("GET", "/api/screen/students", OBSERVER),
+    ("GET", "/ws/screen/view", OBSERVER),
+    ("GET", "/api/classrooms", OBSERVER),
+    ("GET", "/api/classrooms/:id", OBSERVER),
+    ("GET", "/api/classrooms/:id/students", OBSERVER),
+    ("GET", "/api/layouts", OBSERVER),
+    ("GET", "/api/layouts/:room", OBSERVER),
+    ("GET", "/ws", Access::RoleOrAgent(Role::Observer)),
+    // Own password; admins may change anyone's (checked in the handler)
+    ("PUT", "/api/teachers/:username/password", OBSERVER),
+    // Teachers: classroom actions
+    ("GET", "/api/violations", TEACHER),
+    ("GET", "/api/violations/export", TEACHER),
+    ("GET", "/api/config", TEACHER),
+    ("GET", "/api/schedule", TEACHER),
+    ("POST", "/api/students/:hostname/lock", TEACHER),
+    ("POST", "/api/students/:hostname/unlock", TEACHER),
+    ("POST", "/api/students/:hostname/open-url", TEACHER),
+    ("GET", "/api/apps/:hostname", TEACHER),
+    ("POST", "/api/broadcast/open-url", TEACHER),
+    ("POST", "/api/broadcast/lock", TEACHER),
+    ("POST", "/api/broadcast/unlock", TEACHER),
+    ("POST", "/api/students/lock", TEACHER),
+    ("POST", "/api/students/unlock", TEACHER),
+    ("POST", "/api/students/open-url", TEACHER),
+    ("GET", "/api/groups", TEACHER),
+    ("POST", "/api/groups", TEACHER),
+    ("GET", "/api/groups/:name", TEACHER),
+    ("DELETE", "/api/groups/:name", TEACHER),
+    ("PUT", "/api/groups/:name/members", TEACHER),
+    ("POST", "/api/groups/:name/members", TEACHER),
+    ("DELETE", "/api/groups/:name/members/:hostname", TEACHER),
+    ("POST", "/api/groups/:name/lock", TEACHER),
+    ("POST", "/api/groups/:name/unlock", TEACHER),
+    ("POST", "/api/groups/:name/open-url", TEACHER),
+    ("GET", "/api/agents/throttle", TEACHER),
+    ("GET", "/api/classrooms/:id/violations", TEACHER),
+    ("GET", "/api/access-requests", TEACHER),
+    ("POST", "/api/access-requests/:id/approve", TEACHER),
+    ("POST", "/api/access-requests/:id/deny", TEACHER),
+    ("GET", "/api/overrides", TEACHER),
+    ("POST", "/api/overrides", TEACHER),
+    ("DELETE", "/api/overrides/:id", TEACHER),
+    ("GET", "/api/commands", TEACHER),
+    ("GET", "/api/commands/:id", TEACHER),
+    ("GET", "/api/roster", TEACHER),
+    ("PUT", "/api/roster/:id/assignment", TEACHER),
+    ("GET", "/api/sessions", TEACHER),
+    ("POST", "/api/sessions/start", TEACHER),
+    ("POST", "/api/sessions/end", TEACHER),
+    ("GET", "/api/sessions/active", TEACHER),
+    ("GET", "/api/sessions/:id", TEACHER),
+    ("GET", "/api/sessions/:id/violations", TEACHER),
+    ("GET", "/api/sessions/:id/attendance", TEACHER),
+    ("GET", "/api/sessions/:id/activity", TEACHER),
+    // Admins: policy, accounts, enrollment, classrooms, layouts, roster
+    ("PUT", "/api/config", ADMIN),
+    ("GET", "/api/audit", ADMIN),
+    ("GET", "/api/teachers", ADMIN),
+    ("POST", "/api/teachers", ADMIN),
+    ("DELETE", "/api/teachers/:username", ADMIN),
+    ("PUT", "/api/teachers/:username/role", ADMIN),
+    ("POST", "/api/agents/enroll", ADMIN),
+    ("GET", "/api/agents/enrolled", ADMIN),
+    ("DELETE", "/api/agents/:hostname/enrollment", ADMIN),
+    ("POST", "/api/classrooms", ADMIN),
+    ("PUT", "/api/classrooms/:id", ADMIN),
+    ("DELETE", "/api/classrooms/:id", ADMIN),
+    ("PUT", "/api/classrooms/:id/students", ADMIN),
+    ("DELETE", "/api/classrooms/:id/students/:hostname", ADMIN),
+    ("PUT", "/api/layouts/:room", ADMIN),
+    ("DELETE", "/api/layouts/:room", ADMIN),
+    ("POST", "/api/roster", ADMIN),
+    ("POST", "/api/roster/import", ADMIN),
+    ("DELETE", "/api/roster/:id", ADMIN),
+    // Agent ingestion
+    ("POST", "/api/agent/heartbeat", Agent),
+    ("POST", "/api/agent/screenshot", Agent),
+    ("POST", "/api/agent/notification", Agent),
+    ("POST", "/api/agent/apps", Agent),
+    ("POST", "/api/agent/violation", Agent),
+    ("POST", "/api/agent/access-request", Agent),
+    ("POST", "/api/agent/command-ack", Agent),
+    ("GET", "/ws/screen", Agent),
+    ("GET", "/ws/agent", Agent),
+];
+
+/// Look up the access rule for a matched route.
+pub fn required_access(method: &Method, route: &str) -> Option<Access> {
+    PERMISSIONS
+        .iter()
+        .find(|(m, r, _)| *m == method.as_str() && *r == route)
+        .map(|(_, _, access)| *access)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+    /// (method, route) for every `.route(...)` in main.rs, as matched at runtime
+    fn registered_routes() -> Vec<(String, String)> {
+        let source = include_str!("main.rs");
+        let mut routes = Vec::new();
+        for (start, _) in source.match_indices(".route(") {
+            let call = &source[start + ".route(".len()..];
+            let mut depth = 1;
+            let end = call
+                .char_indices()
+                .find(|&(_, c)| {
+                    depth += match c {
+                        '(' => 1,
+                        ')' => -1,
+                        _ => 0,
+                    };
+                    depth == 0
+                })
+                .map(|(i, _)| i)
+                .expect("unbalanced .route(");
+            let call = &call[..end];
+
+            let path = call.split('"').nth(1).expect("route without a path");
+            let path = if path.starts_with("/ws") { path.to_string() } else { format!("/api{path}") };
+            let bytes = call.as_bytes();
+            for method in METHODS {
+                for (i, _) in call.match_indices(&format!("{method}(")) {
+                    let before = if i == 0 { b' ' } else { bytes[i - 1] };
+                    if !before.is_ascii_alphanumeric() && before != b'_' && before != b':' {
+                        routes.push((method.to_uppercase(), path.clone()));
+                    }
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_has_a_permission_entry() {
+        let routes = registered_routes();
+        assert!(routes.len() > 50, "only found {} routes in main.rs", routes.len());
+        for (method, route) in &routes {
+            let method = Method::from_bytes(method.as_bytes()).unwrap();
+            assert!(required_access(&method, route).is_some(), "{method} {route} is missing from PERMISSIONS");
+        }
+    }
+
+    #[test]
+    fn every_permission_entry_has_a_route() {
+        let routes = registered_routes();
+        for (method, route, _) in PERMISSIONS {
+            assert!(
+                routes.iter().any(|(m, r)| m == method && r == route),
+                "{method} {route} in PERMISSIONS is not registered in main.rs"
+            );
+        }
+    }
+}
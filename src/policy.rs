@@ -0,0 +1,117 @@
+// ─────────────────────────────────────────────────────────────────
+//  policy.rs — Publishing ban policies to Redis for student agents
+//
+//  Global:        {prefix}:ban_config, {prefix}:sau_mode, {prefix}:exam_config
+//  Per classroom: the same keys suffixed with :{room}
+//  Agents find their room in {prefix}:student_classroom:{hostname}.
+//
+//  The global policy is the config.toml lists (changed by PUT
+//  /api/config), unless the timetable has switched to a named
+//  profile (see schedule.rs).
+// ─────────────────────────────────────────────────────────────────
+
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+use crate::config::Config;
+use crate::models::BanPolicy;
+use crate::overrides;
+use crate::redis_store;
+use crate::state::AppState;
+
+impl BanPolicy {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            banned_sites: cfg.banned_sites.clone(),
+            banned_apps: cfg.banned_apps.clone(),
+            sau_mode: cfg.sau_mode,
+            exam_mode: cfg.exam_mode,
+            allowed_sites: cfg.allowed_sites.clone(),
+            allowed_apps: cfg.allowed_apps.clone(),
+        }
+    }
+
+    /// Write these lists back into a config (for GET /api/config and config.toml)
+    pub fn apply_to(&self, cfg: &mut Config) {
+        cfg.banned_sites = self.banned_sites.clone();
+        cfg.banned_apps = self.banned_apps.clone();
+        cfg.sau_mode = self.sau_mode;
+        cfg.exam_mode = self.exam_mode;
+        cfg.allowed_sites = self.allowed_sites.clone();
+        cfg.allowed_apps = self.allowed_apps.clone();
+    }
+}
+
+/// Global policy as it stands at runtime
+#[derive(Debug, Clone)]
+pub struct PolicyState {
+    /// The config.toml lists, as last saved
+    pub base: BanPolicy,
+    /// Timetable profile currently in force, if any
+    pub active_profile: Option<String>,
+}
+
+impl PolicyState {
+    pub fn new(cfg: &Config) -> Self {
+        Self { base: BanPolicy::from_config(cfg), active_profile: None }
+    }
+
+    /// What agents should enforce right now
+    pub fn effective(&self, cfg: &Config) -> BanPolicy {
+        self.active_profile
+            .as_ref()
+            .and_then(|name| cfg.policy_profiles.get(name))
+            .cloned()
+            .unwrap_or_else(|| self.base.clone())
+    }
+}
+
+/// Publish `policy` globally (`room == None`) or for one classroom.
+pub async fn publish(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    room: Option<&str>,
+    policy: &BanPolicy,
+) -> redis::RedisResult<()> {
+    let suffix = room.map(|r| format!(":{r}")).unwrap_or_default();
+
+    let ban_json = serde_json::json!({
+        "banned_processes": policy.banned_apps,
+        "banned_domains": policy.banned_sites,
+    });
+    let _: () = conn.set(format!("{prefix}:ban_config{suffix}"), ban_json.to_string()).await?;
+    let _: () = conn
+        .set(format!("{prefix}:sau_mode{suffix}"), if policy.sau_mode { "1" } else { "0" })
+        .await?;
+
+    let exam_json = serde_json::json!({
+        "enabled": policy.exam_mode,
+        "allowed_processes": policy.allowed_apps,
+        "allowed_domains": policy.allowed_sites,
+    });
+    let _: () = conn.set(format!("{prefix}:exam_config{suffix}"), exam_json.to_string()).await?;
+    Ok(())
+}
+
+/// The policy `hostname` is under: its classroom's (else the global one),
+/// with any overrides for the machine or its current user applied.
+pub async fn policy_for(state: &AppState, conn: &mut ConnectionManager, hostname: &str) -> BanPolicy {
+    let prefix = &state.config.key_prefix;
+    let room = state.student_rooms.get(hostname).map(|r| r.clone());
+    let mut policy = None;
+    if let Some(room) = room {
+        if let Ok(Some(classroom)) = redis_store::get_classroom(conn, prefix, &room).await {
+            policy = Some(classroom.policy);
+        }
+    }
+    let policy = policy.unwrap_or_else(|| state.effective_policy());
+
+    let username = redis_store::get_heartbeat(conn, prefix, hostname)
+        .await
+        .ok()
+        .flatten()
+        .map(|hb| hb.username)
+        .unwrap_or_default();
+    let active = overrides::active_for(state, conn, hostname, &username).await;
+    policy.with_overrides(&active)
+}
@@ -0,0 +1,149 @@
+// ─────────────────────────────────────────────────────────────────
+//  rate_limit.rs — Per-hostname token buckets for agent ingestion,
+//  plus counters of who is being throttled. Oversized bodies are
+//  refused before the signature can be checked, so they are counted
+//  per client address, in a bounded map.
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::time::Instant;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Client addresses tracked for oversized requests; the stalest is dropped beyond this
+const MAX_OVERSIZED_PEERS: usize = 1024;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ThrottleStats {
+    /// Requests refused with 429
+    pub rate_limited: u64,
+    pub last_throttled: Option<DateTime<Utc>>,
+}
+
+/// Requests refused with 413 from one client address
+#[derive(Debug, Clone, Serialize)]
+pub struct OversizedStats {
+    pub count: u64,
+    pub last_at: DateTime<Utc>,
+    /// Hostname header of the last one; not verified
+    pub claimed_hostname: String,
+}
+
+pub struct RateLimiter {
+    per_sec: f64,
+    burst: f64,
+    buckets: DashMap<String, Bucket>,
+    stats: DashMap<String, ThrottleStats>,
+    oversized: DashMap<IpAddr, OversizedStats>,
+}
+
+impl RateLimiter {
+    pub fn new(per_sec: f64, burst: u32) -> Self {
+        Self {
+            per_sec,
+            burst: f64::from(burst.max(1)),
+            buckets: DashMap::new(),
+            stats: DashMap::new(),
+            oversized: DashMap::new(),
+        }
+    }
+
+    /// Take one token for `hostname`; returns false (and counts it) when empty.
+    pub fn try_acquire(&self, hostname: &str) -> bool {
+        let now = Instant::now();
+        let mut bucket = self.buckets.entry(hostname.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            refilled_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_sec).min(self.burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            drop(bucket);
+            let mut stats = self.stats.entry(hostname.to_string()).or_default();
+            stats.rate_limited += 1;
+            stats.last_throttled = Some(Utc::now());
+            false
+        }
+    }
+
+    pub fn record_oversized(&self, peer: IpAddr, claimed_hostname: &str) {
+        if !self.oversized.contains_key(&peer) && self.oversized.len() >= MAX_OVERSIZED_PEERS {
+            let stalest = self.oversized.iter().min_by_key(|e| e.last_at).map(|e| *e.key());
+            if let Some(stalest) = stalest {
+                self.oversized.remove(&stalest);
+            }
+        }
+        let now = Utc::now();
+        let mut stats = self.oversized.entry(peer).or_insert(OversizedStats {
+            count: 0,
+            last_at: now,
+            claimed_hostname: String::new(),
+        });
+        stats.count += 1;
+        stats.last_at = now;
+        stats.claimed_hostname = claimed_hostname.to_string();
+    }
+
+    /// Seconds until the next token is available
+    pub fn retry_after_secs(&self) -> u64 {
+        if self.per_sec > 0.0 { (1.0 / self.per_sec).ceil() as u64 } else { 60 }
+    }
+
+    pub fn oversized_snapshot(&self) -> Vec<(IpAddr, OversizedStats)> {
+        self.oversized.iter().map(|e| (*e.key(), e.value().clone())).collect()
+    }
+
+    pub fn snapshot(&self) -> Vec<(String, ThrottleStats)> {
+        self.stats
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_then_refused_per_host() {
+        let limiter = RateLimiter::new(0.0, 3);
+        assert!((0..3).all(|_| limiter.try_acquire("LAB2-PC01")));
+        assert!(!limiter.try_acquire("LAB2-PC01"));
+        assert!(limiter.try_acquire("LAB2-PC02"));
+
+        let stats = limiter.snapshot();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].0, "LAB2-PC01");
+        assert_eq!(stats[0].1.rate_limited, 1);
+    }
+
+    #[test]
+    fn tokens_refill_over_time() {
+        let limiter = RateLimiter::new(1000.0, 1);
+        assert!(limiter.try_acquire("LAB2-PC01"));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(limiter.try_acquire("LAB2-PC01"));
+    }
+
+    #[test]
+    fn oversized_peers_are_bounded() {
+        let limiter = RateLimiter::new(1.0, 1);
+        for i in 0..=MAX_OVERSIZED_PEERS as u32 {
+            limiter.record_oversized(IpAddr::from(i.to_be_bytes()), "LAB2-PC01");
+        }
+        assert_eq!(limiter.oversized_snapshot().len(), MAX_OVERSIZED_PEERS);
+    }
+}
@@ -1,5 +1,6 @@
 use redis::aio::ConnectionManager;
 use redis::AsyncCommands;
+use std::collections::HashMap;
 
 use crate::models::*;
 
@@ -27,6 +28,25 @@ pub async fn get_heartbeat(
     Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
 }
 
+/// Heartbeats that haven't expired, of those `hostnames` that have one
+pub async fn get_heartbeats(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    hostnames: &[String],
+) -> R<HashMap<String, Heartbeat>> {
+    if hostnames.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let keys: Vec<String> = hostnames.iter().map(|h| format!("{prefix}:heartbeat:{h}")).collect();
+    let values: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query_async(conn).await?;
+    Ok(values
+        .into_iter()
+        .flatten()
+        .filter_map(|v| serde_json::from_str::<Heartbeat>(&v).ok())
+        .map(|hb| (hb.hostname.clone(), hb))
+        .collect())
+}
+
 // ── Agent registry ───────────────────────────────────────
 pub async fn register_agent(
     conn: &mut ConnectionManager,
@@ -55,6 +75,8 @@ pub async fn add_violation(
     let count_key = format!("{prefix}:violation_count:{}", v.hostname);
     let json = serde_json::to_string(v).unwrap_or_default();
     let _: () = conn.lpush(&list_key, &json).await?;
+    // keep last 1000; the counter keeps the all-time total
+    let _: () = conn.ltrim(&list_key, 0, 999).await?;
     let _: () = conn.incr(&count_key, 1i64).await?;
     Ok(())
 }
@@ -162,3 +184,885 @@ pub async fn update_server_ip(
     let key = format!("{prefix}:server:ip");
     conn.set_ex(&key, ip, 360u64).await
 }
+
+// ── Teacher accounts ─────────────────────────────────────
+pub async fn store_teacher(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    account: &TeacherAccount,
+) -> R<()> {
+    let key = format!("{prefix}:teachers");
+    let json = serde_json::to_string(account).unwrap_or_default();
+    conn.hset(&key, &account.username, &json).await
+}
+
+pub async fn get_teacher(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    username: &str,
+) -> R<Option<TeacherAccount>> {
+    let key = format!("{prefix}:teachers");
+    let val: Option<String> = conn.hget(&key, username).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn get_all_teachers(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+) -> R<Vec<TeacherAccount>> {
+    let key = format!("{prefix}:teachers");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn delete_teacher(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    username: &str,
+) -> R<bool> {
+    let key = format!("{prefix}:teachers");
+    let removed: i64 = conn.hdel(&key, username).await?;
+    Ok(removed > 0)
+}
+
+// ── Teacher login sessions ───────────────────────────────
+pub async fn store_auth_session(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    token: &str,
+    username: &str,
+    ttl: u64,
+) -> R<()> {
+    let key = format!("{prefix}:auth_session:{token}");
+    let index = format!("{prefix}:auth_sessions:{username}");
+    redis::pipe()
+        .set_ex(&key, username, ttl)
+        .ignore()
+        .sadd(&index, token)
+        .ignore()
+        .expire(&index, ttl as i64)
+        .ignore()
+        .query_async(conn)
+        .await
+}
+
+pub async fn get_auth_session(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    token: &str,
+) -> R<Option<String>> {
+    let key = format!("{prefix}:auth_session:{token}");
+    conn.get(&key).await
+}
+
+/// Delete session `token` of `username`, together with its entry in the
+/// user's session index.
+pub async fn delete_auth_session(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    username: &str,
+    token: &str,
+) -> R<()> {
+    let key = format!("{prefix}:auth_session:{token}");
+    let index = format!("{prefix}:auth_sessions:{username}");
+    redis::pipe()
+        .atomic()
+        .del(&key)
+        .ignore()
+        .srem(&index, token)
+        .ignore()
+        .query_async(conn)
+        .await
+}
+
+/// Revoke every session of `username` except `keep`; returns how many went.
+pub async fn revoke_auth_sessions(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    username: &str,
+    keep: Option<&str>,
+) -> R<usize> {
+    let index = format!("{prefix}:auth_sessions:{username}");
+    let tokens: Vec<String> = conn.smembers(&index).await?;
+    let mut revoked = 0;
+    for token in tokens.iter().filter(|t| Some(t.as_str()) != keep) {
+        let _: () = conn.del(format!("{prefix}:auth_session:{token}")).await?;
+        let _: () = conn.srem(&index, token).await?;
+        revoked += 1;
+    }
+    Ok(revoked)
+}
+
+// ── Failed login counters ────────────────────────────────
+/// Count a failed login against `subject` ("user:<name>" or "ip:<addr>");
+/// the count resets `window` seconds after the first failure.
+pub async fn add_login_failure(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    subject: &str,
+    window: u64,
+) -> R<u32> {
+    incr_with_ttl(conn, &format!("{prefix}:login_failures:{subject}"), window).await
+}
+
+/// Increment `key`, starting its `ttl` on the first increment. One script,
+/// so a counter can never be left behind without an expiry.
+async fn incr_with_ttl(conn: &mut ConnectionManager, key: &str, ttl: u64) -> R<u32> {
+    let script = redis::Script::new(
+        r"
+        local count = redis.call('INCR', KEYS[1])
+        if count == 1 then redis.call('EXPIRE', KEYS[1], ARGV[1]) end
+        return count
+        ",
+    );
+    script.key(key).arg(ttl).invoke_async(conn).await
+}
+
+pub async fn get_login_failures(conn: &mut ConnectionManager, prefix: &str, subject: &str) -> R<u32> {
+    let count: Option<u32> = conn.get(format!("{prefix}:login_failures:{subject}")).await?;
+    Ok(count.unwrap_or(0))
+}
+
+pub async fn clear_login_failures(conn: &mut ConnectionManager, prefix: &str, subject: &str) -> R<()> {
+    conn.del(format!("{prefix}:login_failures:{subject}")).await
+}
+
+// ── Agent enrollment ─────────────────────────────────────
+pub async fn store_enrollment(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    enrollment: &AgentEnrollment,
+) -> R<()> {
+    let key = format!("{prefix}:agent_secrets");
+    let json = serde_json::to_string(enrollment).unwrap_or_default();
+    conn.hset(&key, &enrollment.hostname, &json).await
+}
+
+pub async fn get_enrollment(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    hostname: &str,
+) -> R<Option<AgentEnrollment>> {
+    let key = format!("{prefix}:agent_secrets");
+    let val: Option<String> = conn.hget(&key, hostname).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn get_all_enrollments(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+) -> R<Vec<AgentEnrollment>> {
+    let key = format!("{prefix}:agent_secrets");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn delete_enrollment(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    hostname: &str,
+) -> R<bool> {
+    let key = format!("{prefix}:agent_secrets");
+    let removed: i64 = conn.hdel(&key, hostname).await?;
+    Ok(removed > 0)
+}
+
+/// Remember a request signature; returns false if it was already seen.
+pub async fn claim_agent_nonce(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    hostname: &str,
+    signature: &str,
+    ttl: u64,
+) -> R<bool> {
+    let key = format!("{prefix}:agent_nonce:{hostname}:{signature}");
+    let claimed: Option<String> = redis::cmd("SET")
+        .arg(&key)
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(ttl)
+        .query_async(conn)
+        .await?;
+    Ok(claimed.is_some())
+}
+
+// ── Audit trail ──────────────────────────────────────────
+/// Add an entry and drop those older than `retain_from_ms`
+pub async fn add_audit_entry(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    entry: &AuditEntry,
+    retain_from_ms: i64,
+) -> R<()> {
+    let key = format!("{prefix}:audit");
+    let json = serde_json::to_string(entry).unwrap_or_default();
+    redis::pipe()
+        .zadd(&key, &json, entry.timestamp.timestamp_millis())
+        .ignore()
+        .zrembyscore(&key, "-inf", format!("({retain_from_ms}"))
+        .ignore()
+        .query_async(conn)
+        .await
+}
+
+/// Entries with `from_ms <= timestamp <= to_ms`, newest first
+pub async fn get_audit_entries(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    from_ms: i64,
+    to_ms: i64,
+) -> R<Vec<AuditEntry>> {
+    let key = format!("{prefix}:audit");
+    let items: Vec<String> = conn.zrevrangebyscore(&key, to_ms, from_ms).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+// ── Classrooms ───────────────────────────────────────────
+pub async fn store_classroom(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    room: &Classroom,
+) -> R<()> {
+    let key = format!("{prefix}:classrooms");
+    let json = serde_json::to_string(room).unwrap_or_default();
+    conn.hset(&key, &room.id, &json).await
+}
+
+pub async fn get_classroom(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+) -> R<Option<Classroom>> {
+    let key = format!("{prefix}:classrooms");
+    let val: Option<String> = conn.hget(&key, id).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn get_all_classrooms(conn: &mut ConnectionManager, prefix: &str) -> R<Vec<Classroom>> {
+    let key = format!("{prefix}:classrooms");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+/// Remove a classroom, its published policy, its layout and all of its seat assignments.
+pub async fn delete_classroom(conn: &mut ConnectionManager, prefix: &str, id: &str) -> R<bool> {
+    let members = get_classroom_members(conn, prefix, id).await?;
+    let mut keys: Vec<String> = members
+        .iter()
+        .map(|hostname| format!("{prefix}:student_classroom:{hostname}"))
+        .collect();
+    keys.extend([
+        format!("{prefix}:classroom_members:{id}"),
+        format!("{prefix}:ban_config:{id}"),
+        format!("{prefix}:sau_mode:{id}"),
+        format!("{prefix}:exam_config:{id}"),
+    ]);
+    let (removed,): (i64,) = redis::pipe()
+        .atomic()
+        .del(&keys)
+        .ignore()
+        .hdel(format!("{prefix}:layouts"), id)
+        .ignore()
+        .hdel(format!("{prefix}:classrooms"), id)
+        .query_async(conn)
+        .await?;
+    Ok(removed > 0)
+}
+
+/// Put a student in a classroom, moving them out of any previous one.
+pub async fn assign_student(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    hostname: &str,
+) -> R<()> {
+    if let Some(previous) = get_student_classroom(conn, prefix, hostname).await? {
+        let _: () = conn.srem(format!("{prefix}:classroom_members:{previous}"), hostname).await?;
+    }
+    let _: () = conn.sadd(format!("{prefix}:classroom_members:{id}"), hostname).await?;
+    conn.set(format!("{prefix}:student_classroom:{hostname}"), id).await
+}
+
+pub async fn unassign_student(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    hostname: &str,
+) -> R<bool> {
+    let removed: i64 = conn.srem(format!("{prefix}:classroom_members:{id}"), hostname).await?;
+    if removed > 0 {
+        let _: () = conn.del(format!("{prefix}:student_classroom:{hostname}")).await?;
+    }
+    Ok(removed > 0)
+}
+
+pub async fn get_classroom_members(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+) -> R<Vec<String>> {
+    conn.smembers(format!("{prefix}:classroom_members:{id}")).await
+}
+
+pub async fn get_student_classroom(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    hostname: &str,
+) -> R<Option<String>> {
+    conn.get(format!("{prefix}:student_classroom:{hostname}")).await
+}
+
+// ── Student groups ───────────────────────────────────────
+pub async fn store_group(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    group: &StudentGroup,
+) -> R<()> {
+    let key = format!("{prefix}:groups");
+    let json = serde_json::to_string(group).unwrap_or_default();
+    conn.hset(&key, &group.name, &json).await
+}
+
+pub async fn get_group(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    name: &str,
+) -> R<Option<StudentGroup>> {
+    let key = format!("{prefix}:groups");
+    let val: Option<String> = conn.hget(&key, name).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn get_all_groups(conn: &mut ConnectionManager, prefix: &str) -> R<Vec<StudentGroup>> {
+    let key = format!("{prefix}:groups");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn delete_group(conn: &mut ConnectionManager, prefix: &str, name: &str) -> R<bool> {
+    let _: () = conn.del(format!("{prefix}:group:{name}")).await?;
+    let removed: i64 = conn.hdel(format!("{prefix}:groups"), name).await?;
+    Ok(removed > 0)
+}
+
+pub async fn get_group_members(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    name: &str,
+) -> R<Vec<String>> {
+    conn.smembers(format!("{prefix}:group:{name}")).await
+}
+
+/// Replace the group's members with `hostnames`.
+pub async fn set_group_members(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    name: &str,
+    hostnames: &[String],
+) -> R<()> {
+    let key = format!("{prefix}:group:{name}");
+    let _: () = conn.del(&key).await?;
+    if hostnames.is_empty() {
+        return Ok(());
+    }
+    conn.sadd(&key, hostnames).await
+}
+
+pub async fn add_group_members(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    name: &str,
+    hostnames: &[String],
+) -> R<()> {
+    if hostnames.is_empty() {
+        return Ok(());
+    }
+    conn.sadd(format!("{prefix}:group:{name}"), hostnames).await
+}
+
+pub async fn remove_group_member(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    name: &str,
+    hostname: &str,
+) -> R<bool> {
+    let removed: i64 = conn.srem(format!("{prefix}:group:{name}"), hostname).await?;
+    Ok(removed > 0)
+}
+
+// ── Lesson sessions ──────────────────────────────────────
+pub async fn store_lesson(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    lesson: &LessonSession,
+) -> R<()> {
+    let key = format!("{prefix}:lessons");
+    let json = serde_json::to_string(lesson).unwrap_or_default();
+    conn.hset(&key, &lesson.id, &json).await
+}
+
+pub async fn get_lesson(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+) -> R<Option<LessonSession>> {
+    let key = format!("{prefix}:lessons");
+    let val: Option<String> = conn.hget(&key, id).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn get_all_lessons(conn: &mut ConnectionManager, prefix: &str) -> R<Vec<LessonSession>> {
+    let key = format!("{prefix}:lessons");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+/// Running lessons by scope ("all" or a classroom id) -> lesson id
+pub async fn get_active_lessons(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+) -> R<Vec<(String, String)>> {
+    conn.hgetall(format!("{prefix}:lesson:active")).await
+}
+
+pub async fn set_active_lesson(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    scope: &str,
+    id: Option<&str>,
+) -> R<()> {
+    let key = format!("{prefix}:lesson:active");
+    match id {
+        Some(id) => conn.hset(&key, scope, id).await,
+        None => conn.hdel(&key, scope).await,
+    }
+}
+
+pub async fn add_lesson_violation(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    v: &Violation,
+) -> R<()> {
+    let key = format!("{prefix}:lesson:{id}:violations");
+    let json = serde_json::to_string(v).unwrap_or_default();
+    conn.rpush(&key, &json).await
+}
+
+pub async fn get_lesson_violations(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+) -> R<Vec<Violation>> {
+    let key = format!("{prefix}:lesson:{id}:violations");
+    let items: Vec<String> = conn.lrange(&key, 0, -1).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn add_lesson_notification(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    n: &Notification,
+) -> R<()> {
+    let key = format!("{prefix}:lesson:{id}:notifications");
+    let json = serde_json::to_string(n).unwrap_or_default();
+    conn.rpush(&key, &json).await
+}
+
+pub async fn get_lesson_notifications(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+) -> R<Vec<Notification>> {
+    let key = format!("{prefix}:lesson:{id}:notifications");
+    let items: Vec<String> = conn.lrange(&key, 0, -1).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn store_lesson_apps(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    apps: &AppList,
+) -> R<()> {
+    let key = format!("{prefix}:lesson:{id}:apps:{}", apps.hostname);
+    let json = serde_json::to_string(apps).unwrap_or_default();
+    let _: () = conn.lpush(&key, &json).await?;
+    // keep last 200 snapshots per student
+    let _: () = conn.ltrim(&key, 0, 199).await?;
+    Ok(())
+}
+
+pub async fn get_lesson_apps(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    hostname: &str,
+    count: isize,
+) -> R<Vec<AppList>> {
+    let key = format!("{prefix}:lesson:{id}:apps:{hostname}");
+    let items: Vec<String> = conn.lrange(&key, 0, count - 1).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+/// Count one ingested item of `kind` (heartbeat, screenshot, ...) for a student.
+pub async fn incr_lesson_activity(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    hostname: &str,
+    kind: &str,
+) -> R<()> {
+    let key = format!("{prefix}:lesson:{id}:activity:{hostname}");
+    conn.hincr(&key, kind, 1i64).await
+}
+
+pub async fn get_lesson_activity(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    hostname: &str,
+) -> R<Vec<(String, i64)>> {
+    conn.hgetall(format!("{prefix}:lesson:{id}:activity:{hostname}")).await
+}
+
+pub async fn store_attendance(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    record: &AttendanceRecord,
+) -> R<()> {
+    let key = format!("{prefix}:lesson:{id}:attendance");
+    let json = serde_json::to_string(record).unwrap_or_default();
+    conn.hset(&key, &record.hostname, &json).await
+}
+
+pub async fn get_attendance(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+    hostname: &str,
+) -> R<Option<AttendanceRecord>> {
+    let key = format!("{prefix}:lesson:{id}:attendance");
+    let val: Option<String> = conn.hget(&key, hostname).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn get_all_attendance(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+) -> R<Vec<AttendanceRecord>> {
+    let key = format!("{prefix}:lesson:{id}:attendance");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+// ── Policy overrides ─────────────────────────────────────
+pub async fn store_override(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    entry: &PolicyOverride,
+) -> R<()> {
+    let key = format!("{prefix}:overrides");
+    let json = serde_json::to_string(entry).unwrap_or_default();
+    conn.hset(&key, &entry.id, &json).await
+}
+
+pub async fn get_all_overrides(conn: &mut ConnectionManager, prefix: &str) -> R<Vec<PolicyOverride>> {
+    let key = format!("{prefix}:overrides");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn get_override(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+) -> R<Option<PolicyOverride>> {
+    let key = format!("{prefix}:overrides");
+    let val: Option<String> = conn.hget(&key, id).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn delete_override(conn: &mut ConnectionManager, prefix: &str, id: &str) -> R<bool> {
+    let removed: i64 = conn.hdel(format!("{prefix}:overrides"), id).await?;
+    Ok(removed > 0)
+}
+
+/// Publish the merged overrides for one subject ("host:{hostname}" or
+/// "user:{username}"); `None` removes them.
+pub async fn set_published_override(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    subject: &str,
+    json: Option<&str>,
+) -> R<()> {
+    let key = format!("{prefix}:policy_override:{subject}");
+    match json {
+        Some(json) => conn.set(&key, json).await,
+        None => conn.del(&key).await,
+    }
+}
+
+// ── Access requests ──────────────────────────────────────
+pub async fn store_access_request(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    req: &AccessRequest,
+) -> R<()> {
+    let key = format!("{prefix}:access_requests");
+    let json = serde_json::to_string(req).unwrap_or_default();
+    conn.hset(&key, &req.id, &json).await
+}
+
+pub async fn get_access_request(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+) -> R<Option<AccessRequest>> {
+    let key = format!("{prefix}:access_requests");
+    let val: Option<String> = conn.hget(&key, id).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn get_all_access_requests(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+) -> R<Vec<AccessRequest>> {
+    let key = format!("{prefix}:access_requests");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn delete_access_requests(conn: &mut ConnectionManager, prefix: &str, ids: &[String]) -> R<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    conn.hdel(format!("{prefix}:access_requests"), ids).await
+}
+
+/// Count a new access request from `hostname`; the count resets an hour
+/// after the first one.
+pub async fn add_access_request_count(conn: &mut ConnectionManager, prefix: &str, hostname: &str) -> R<u32> {
+    incr_with_ttl(conn, &format!("{prefix}:access_request_count:{hostname}"), 3600).await
+}
+
+// ── Roster ───────────────────────────────────────────────
+pub async fn store_roster_student(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    student: &RosterStudent,
+) -> R<()> {
+    let key = format!("{prefix}:roster");
+    let json = serde_json::to_string(student).unwrap_or_default();
+    conn.hset(&key, &student.id, &json).await
+}
+
+pub async fn get_roster(conn: &mut ConnectionManager, prefix: &str) -> R<Vec<RosterStudent>> {
+    let key = format!("{prefix}:roster");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn delete_roster_student(conn: &mut ConnectionManager, prefix: &str, id: &str) -> R<bool> {
+    let removed: i64 = conn.hdel(format!("{prefix}:roster"), id).await?;
+    Ok(removed > 0)
+}
+
+pub async fn clear_roster(conn: &mut ConnectionManager, prefix: &str) -> R<()> {
+    conn.del(format!("{prefix}:roster")).await
+}
+
+// ── Room layouts ─────────────────────────────────────────
+pub async fn store_layout(conn: &mut ConnectionManager, prefix: &str, layout: &RoomLayout) -> R<()> {
+    let key = format!("{prefix}:layouts");
+    let json = serde_json::to_string(layout).unwrap_or_default();
+    conn.hset(&key, &layout.room, &json).await
+}
+
+pub async fn get_layout(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    room: &str,
+) -> R<Option<RoomLayout>> {
+    let key = format!("{prefix}:layouts");
+    let val: Option<String> = conn.hget(&key, room).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn get_all_layouts(conn: &mut ConnectionManager, prefix: &str) -> R<Vec<RoomLayout>> {
+    let key = format!("{prefix}:layouts");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn delete_layout(conn: &mut ConnectionManager, prefix: &str, room: &str) -> R<bool> {
+    let removed: i64 = conn.hdel(format!("{prefix}:layouts"), room).await?;
+    Ok(removed > 0)
+}
+
+// ── Lock state ───────────────────────────────────────────
+pub async fn store_lock_state(conn: &mut ConnectionManager, prefix: &str, lock: &LockState) -> R<()> {
+    let key = format!("{prefix}:lock_states");
+    let json = serde_json::to_string(lock).unwrap_or_default();
+    conn.hset(&key, &lock.hostname, &json).await
+}
+
+pub async fn get_lock_state(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    hostname: &str,
+) -> R<Option<LockState>> {
+    let key = format!("{prefix}:lock_states");
+    let val: Option<String> = conn.hget(&key, hostname).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+/// Hosts with a lock recorded
+pub async fn get_locked_hosts(conn: &mut ConnectionManager, prefix: &str) -> R<Vec<String>> {
+    conn.hkeys(format!("{prefix}:lock_states")).await
+}
+
+pub async fn delete_lock_state(conn: &mut ConnectionManager, prefix: &str, hostname: &str) -> R<bool> {
+    let removed: i64 = conn.hdel(format!("{prefix}:lock_states"), hostname).await?;
+    Ok(removed > 0)
+}
+
+/// Schedule the release of a timed lock (scored by Unix seconds)
+pub async fn schedule_lock_expiry(conn: &mut ConnectionManager, prefix: &str, hostname: &str, at: i64) -> R<()> {
+    conn.zadd(format!("{prefix}:lock_expiry"), hostname, at).await
+}
+
+pub async fn cancel_lock_expiry(conn: &mut ConnectionManager, prefix: &str, hostname: &str) -> R<()> {
+    conn.zrem(format!("{prefix}:lock_expiry"), hostname).await
+}
+
+/// Hostnames whose timed lock is due for release at `now` (Unix seconds)
+pub async fn get_due_lock_expiries(conn: &mut ConnectionManager, prefix: &str, now: i64) -> R<Vec<String>> {
+    conn.zrangebyscore(format!("{prefix}:lock_expiry"), "-inf", now).await
+}
+
+// ── Command queue ────────────────────────────────────────
+pub async fn store_command(conn: &mut ConnectionManager, prefix: &str, cmd: &AgentCommand) -> R<()> {
+    let key = format!("{prefix}:commands");
+    let json = serde_json::to_string(cmd).unwrap_or_default();
+    conn.hset(&key, &cmd.id, &json).await
+}
+
+/// Store a command only if its stored status is one of `expected`, so a
+/// command claimed or finished elsewhere in the meantime is left alone.
+pub async fn replace_command(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    cmd: &AgentCommand,
+    expected: &[&str],
+) -> R<bool> {
+    let script = redis::Script::new(
+        r"
+        local current = redis.call('HGET', KEYS[1], ARGV[1])
+        if not current then return 0 end
+        local status = cjson.decode(current)['status']
+        for i = 3, #ARGV do
+            if status == ARGV[i] then
+                redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+                return 1
+            end
+        end
+        return 0
+        ",
+    );
+    let json = serde_json::to_string(cmd).unwrap_or_default();
+    let mut invocation = script.key(format!("{prefix}:commands"));
+    invocation.arg(&cmd.id).arg(json);
+    for status in expected {
+        invocation.arg(*status);
+    }
+    let replaced: i32 = invocation.invoke_async(conn).await?;
+    Ok(replaced == 1)
+}
+
+pub async fn get_command(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    id: &str,
+) -> R<Option<AgentCommand>> {
+    let key = format!("{prefix}:commands");
+    let val: Option<String> = conn.hget(&key, id).await?;
+    Ok(val.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+pub async fn get_all_commands(conn: &mut ConnectionManager, prefix: &str) -> R<Vec<AgentCommand>> {
+    let key = format!("{prefix}:commands");
+    let items: Vec<String> = conn.hvals(&key).await?;
+    Ok(items
+        .iter()
+        .filter_map(|s| serde_json::from_str(s).ok())
+        .collect())
+}
+
+pub async fn delete_command(conn: &mut ConnectionManager, prefix: &str, id: &str) -> R<()> {
+    conn.hdel(format!("{prefix}:commands"), id).await
+}
+
+/// Add a command id to the end of a host's queue.
+pub async fn queue_command(conn: &mut ConnectionManager, prefix: &str, hostname: &str, id: &str) -> R<()> {
+    conn.rpush(format!("{prefix}:command_queue:{hostname}"), id).await
+}
+
+/// Command ids waiting for a host, oldest first.
+pub async fn get_queued_commands(
+    conn: &mut ConnectionManager,
+    prefix: &str,
+    hostname: &str,
+) -> R<Vec<String>> {
+    conn.lrange(format!("{prefix}:command_queue:{hostname}"), 0, -1).await
+}
+
+pub async fn dequeue_command(conn: &mut ConnectionManager, prefix: &str, hostname: &str, id: &str) -> R<()> {
+    conn.lrem(format!("{prefix}:command_queue:{hostname}"), 0, id).await
+}
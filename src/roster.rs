@@ -0,0 +1,107 @@
+// ─────────────────────────────────────────────────────────────────
+//  roster.rs — Who is sitting at which machine
+//
+//  Students are kept in the {prefix}:roster hash (id -> JSON) and
+//  mirrored in AppState::roster. A student is found on a machine
+//  by their personal login first, then by the seat they were
+//  assigned to; labs that share one generic account rely on seats.
+//  Each hostname and each login belongs to at most one student.
+// ─────────────────────────────────────────────────────────────────
+
+use crate::csv;
+use crate::models::RosterStudent;
+use crate::redis_store;
+use crate::state::AppState;
+
+/// Load the roster into the cache on startup.
+pub async fn warm_cache(state: &AppState) {
+    let mut conn = state.redis.clone();
+    let roster = redis_store::get_roster(&mut conn, &state.config.key_prefix)
+        .await
+        .unwrap_or_default();
+    for student in roster {
+        state.roster.insert(student.id.clone(), student);
+    }
+}
+
+/// Store `student`, taking its hostname and login away from anyone
+/// else who had them.
+pub async fn save(state: &AppState, student: RosterStudent) -> redis::RedisResult<()> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let clashes: Vec<RosterStudent> = state
+        .roster
+        .iter()
+        .filter(|s| s.id != student.id)
+        .filter(|s| {
+            (student.hostname.is_some() && s.hostname == student.hostname)
+                || same_login(&s.username, &student.username)
+        })
+        .map(|s| s.clone())
+        .collect();
+    for mut other in clashes {
+        if student.hostname.is_some() && other.hostname == student.hostname {
+            other.hostname = None;
+        }
+        if same_login(&other.username, &student.username) {
+            other.username = None;
+        }
+        redis_store::store_roster_student(&mut conn, prefix, &other).await?;
+        state.roster.insert(other.id.clone(), other);
+    }
+
+    redis_store::store_roster_student(&mut conn, prefix, &student).await?;
+    state.roster.insert(student.id.clone(), student);
+    Ok(())
+}
+
+/// Logins match regardless of case, as in `AppState::student_for`
+fn same_login(a: &Option<String>, b: &Option<String>) -> bool {
+    matches!((a, b), (Some(a), Some(b)) if a.eq_ignore_ascii_case(b))
+}
+
+/// Non-empty, trimmed value or `None`
+pub fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Students read from a CSV export, plus one error per rejected row.
+///
+/// The first line is a header naming the columns; `id` and `name` are
+/// required, `email`, `username` and `hostname` are optional and may
+/// come in any order. Unknown columns are ignored.
+pub fn parse_import(text: &str) -> Result<(Vec<RosterStudent>, Vec<String>), String> {
+    let mut records = csv::parse(text).into_iter();
+    let header: Vec<String> = records
+        .next()
+        .ok_or("The file is empty.")?
+        .iter()
+        .map(|h| h.trim().to_lowercase().replace([' ', '-'], "_"))
+        .collect();
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+
+    let id_col = column(&["id", "student_id", "student_number"]).ok_or("Missing 'id' column.")?;
+    let name_col = column(&["name", "full_name", "student_name"]).ok_or("Missing 'name' column.")?;
+    let email_col = column(&["email"]);
+    let username_col = column(&["username", "login"]);
+    let hostname_col = column(&["hostname", "machine", "seat"]);
+
+    let mut students = Vec::new();
+    let mut errors = Vec::new();
+    for (i, record) in records.enumerate() {
+        let get = |col: Option<usize>| col.and_then(|c| record.get(c)).and_then(|v| non_empty(v));
+        match (get(Some(id_col)), get(Some(name_col))) {
+            (Some(id), Some(name)) => students.push(RosterStudent {
+                id,
+                name,
+                email: get(email_col).unwrap_or_default(),
+                username: get(username_col),
+                hostname: get(hostname_col),
+            }),
+            _ => errors.push(format!("Row {}: id and name are required.", i + 1)),
+        }
+    }
+    Ok((students, errors))
+}
@@ -0,0 +1,262 @@
+use axum::extract::{Path, Query, State};
+use axum::extract::ws::Message;
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::agent_auth::AgentIdentity;
+use crate::audit;
+use crate::auth::{self, Identity};
+use crate::models::{AccessRequest, PolicyOverride};
+use crate::overrides;
+use crate::redis_store;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct AccessRequestQuery {
+    /// pending | approved | denied; all when omitted
+    pub status: Option<String>,
+    pub hostname: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct DecisionRequest {
+    /// How long the item stays unblocked; `access_grant_secs` when omitted
+    pub duration_secs: Option<i64>,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// Tell the student's agent (over its /ws connection) how a request went.
+fn push_decision(state: &AppState, req: &AccessRequest, expires_at: Option<chrono::DateTime<Utc>>) {
+    if let Some(agent_tx) = state.ws_clients.get(&req.hostname) {
+        let msg = json!({
+            "type": "access_decision",
+            "id": req.id,
+            "kind": req.kind,
+            "target": req.target,
+            "status": req.status,
+            "note": req.decision_note,
+            "expires_at": expires_at,
+        });
+        let _ = agent_tx.send(Message::Text(msg.to_string()));
+    }
+}
+
+/// Answered requests are dropped after this long
+const DECIDED_RETENTION_DAYS: i64 = 7;
+
+/// POST /api/agent/access-request
+/// Body: { "hostname": "...", "username": "...", "kind": "site", "target": "youtube.com", "reason": "..." }
+/// A student asks for something to be unblocked. Repeating a request that
+/// is still pending returns the existing one. Each machine may have
+/// `access_requests_max_pending` waiting and send `access_requests_per_hour`.
+pub async fn submit(
+    State(state): State<Arc<AppState>>,
+    Extension(agent): Extension<AgentIdentity>,
+    Json(mut req): Json<AccessRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let target = req.target.trim().to_lowercase();
+    if (req.kind != "site" && req.kind != "app") || target.is_empty() {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "kind must be 'site' or 'app' and target is required."
+        })));
+    }
+    // The signed hostname, whatever the body says
+    req.hostname = agent.hostname;
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let all = redis_store::get_all_access_requests(&mut conn, prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let cutoff = Utc::now() - chrono::Duration::days(DECIDED_RETENTION_DAYS);
+    let stale: Vec<String> = all
+        .iter()
+        .filter(|r| r.status != "pending" && r.decided_at.unwrap_or(r.created_at) < cutoff)
+        .map(|r| r.id.clone())
+        .collect();
+    if let Err(e) = redis_store::delete_access_requests(&mut conn, prefix, &stale).await {
+        tracing::warn!("Could not drop old access requests: {e}");
+    }
+
+    let pending: Vec<&AccessRequest> = all
+        .iter()
+        .filter(|r| r.status == "pending" && r.hostname == req.hostname)
+        .collect();
+    if let Some(existing) = pending.iter().find(|r| r.kind == req.kind && r.target == target) {
+        return Ok(Json(json!({ "status": "ok", "id": existing.id })));
+    }
+    if pending.len() >= state.config.access_requests_max_pending {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "Too many requests are waiting for an answer."
+        })));
+    }
+    let sent = redis_store::add_access_request_count(&mut conn, prefix, &req.hostname)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if sent > state.config.access_requests_per_hour {
+        tracing::warn!("🙋 {} is over its access request limit", req.hostname);
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    req.id = auth::new_token()[..12].to_string();
+    req.target = target;
+    req.reason = req.reason.chars().take(500).collect();
+    req.status = "pending".to_string();
+    req.created_at = Utc::now();
+    req.decided_by = None;
+    req.decided_at = None;
+    req.decision_note = String::new();
+    req.override_id = None;
+
+    redis_store::store_access_request(&mut conn, prefix, &req)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tracing::info!("🙋 {} asks to unblock {} '{}'", req.hostname, req.kind, req.target);
+    state.notify_teachers(&json!({
+        "type": "access_request",
+        "id": req.id,
+        "hostname": req.hostname,
+        "username": req.username,
+        "kind": req.kind,
+        "target": req.target,
+        "reason": req.reason,
+        "timestamp": req.created_at.to_rfc3339()
+    }));
+
+    Ok(Json(json!({ "status": "ok", "id": req.id })))
+}
+
+/// GET /api/access-requests?status=pending&hostname=<h> — newest first
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<AccessRequestQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let mut requests: Vec<AccessRequest> =
+        redis_store::get_all_access_requests(&mut conn, &state.config.key_prefix)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .filter(|r| q.status.as_ref().is_none_or(|s| &r.status == s))
+            .filter(|r| q.hostname.as_ref().is_none_or(|h| &r.hostname == h))
+            .collect();
+    requests.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+
+    Ok(Json(json!({
+        "count": requests.len(),
+        "requests": requests,
+    })))
+}
+
+async fn load_pending(state: &AppState, id: &str) -> Result<AccessRequest, StatusCode> {
+    let mut conn = state.redis.clone();
+    let req = redis_store::get_access_request(&mut conn, &state.config.key_prefix, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if req.status != "pending" {
+        return Err(StatusCode::CONFLICT);
+    }
+    Ok(req)
+}
+
+/// POST /api/access-requests/:id/approve
+/// Body: { "duration_secs": 2700, "note": "..." } (both optional)
+/// Creates a time-limited override for the student's machine.
+pub async fn approve(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+    body: Option<Json<DecisionRequest>>,
+) -> Result<Json<Value>, StatusCode> {
+    let Json(body) = body.unwrap_or_default();
+    let mut req = load_pending(&state, &id).await?;
+
+    // An approval is an override, so it is bounded like one
+    let secs = body.duration_secs.unwrap_or(state.config.access_grant_secs);
+    let max_secs = state.config.override_max_secs;
+    let now = Utc::now();
+    let expires_at = chrono::Duration::try_seconds(secs).and_then(|d| now.checked_add_signed(d));
+    let Some(expires_at) = expires_at.filter(|_| secs > 0 && secs <= max_secs) else {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": format!("duration_secs must be between 1 and {max_secs}.")
+        })));
+    };
+
+    let (allow_sites, allow_apps) = match req.kind.as_str() {
+        "site" => (vec![req.target.clone()], vec![]),
+        _ => (vec![], vec![req.target.clone()]),
+    };
+    let entry = PolicyOverride {
+        id: auth::new_token()[..12].to_string(),
+        hostname: Some(req.hostname.clone()),
+        username: None,
+        allow_sites,
+        allow_apps,
+        ban_sites: vec![],
+        ban_apps: vec![],
+        reason: format!("Access request {}: {}", req.id, req.reason),
+        created_by: identity.username.clone(),
+        created_at: now,
+        expires_at: Some(expires_at),
+    };
+    overrides::create(&state, &entry)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    req.status = "approved".to_string();
+    req.decided_by = Some(identity.username.clone());
+    req.decided_at = Some(now);
+    req.decision_note = body.note;
+    req.override_id = Some(entry.id.clone());
+    let mut conn = state.redis.clone();
+    redis_store::store_access_request(&mut conn, &state.config.key_prefix, &req)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    push_decision(&state, &req, Some(expires_at));
+
+    let params = json!({ "id": req.id, "kind": req.kind, "target": req.target, "duration_secs": secs });
+    audit::record(&state, &identity, "approve_access", vec![req.hostname.clone()], params, vec![]).await;
+    tracing::info!("✅ {} approved {} '{}' for {} ({secs}s)", identity.username, req.kind, req.target, req.hostname);
+    Ok(Json(json!(req)))
+}
+
+/// POST /api/access-requests/:id/deny
+/// Body: { "note": "..." } (optional)
+pub async fn deny(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+    body: Option<Json<DecisionRequest>>,
+) -> Result<Json<Value>, StatusCode> {
+    let Json(body) = body.unwrap_or_default();
+    let mut req = load_pending(&state, &id).await?;
+
+    req.status = "denied".to_string();
+    req.decided_by = Some(identity.username.clone());
+    req.decided_at = Some(Utc::now());
+    req.decision_note = body.note;
+    let mut conn = state.redis.clone();
+    redis_store::store_access_request(&mut conn, &state.config.key_prefix, &req)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    push_decision(&state, &req, None);
+
+    let params = json!({ "id": req.id, "kind": req.kind, "target": req.target });
+    audit::record(&state, &identity, "deny_access", vec![req.hostname.clone()], params, vec![]).await;
+    tracing::info!("🚫 {} denied {} '{}' for {}", identity.username, req.kind, req.target, req.hostname);
+    Ok(Json(json!(req)))
+}
@@ -5,7 +5,12 @@ use chrono::Utc;
 use serde_json::{json, Value};
 use std::sync::Arc;
 
+use crate::commands;
+use crate::exam;
+use crate::lessons;
+use crate::lock_state;
 use crate::models::*;
+use crate::policy;
 use crate::redis_store;
 use crate::state::AppState;
 
@@ -15,6 +20,8 @@ pub async fn heartbeat(
     Json(mut hb): Json<Heartbeat>,
 ) -> Result<Json<Value>, StatusCode> {
     hb.timestamp = Utc::now();
+    let lesson = state.lesson_for(&hb.hostname);
+    hb.session_id = lesson.as_ref().map(|l| l.id.clone());
     let mut conn = state.redis.clone();
     let prefix = &state.config.key_prefix;
 
@@ -26,6 +33,20 @@ pub async fn heartbeat(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    if let Some(lesson) = &lesson {
+        lessons::record_heartbeat(&state, &lesson.id, &hb).await;
+    }
+
+    // A locked machine that restarted gets its lock queued again
+    lock_state::reconcile(&state, &hb).await;
+
+    // Commands that could not be delivered while the machine was away
+    let queued = commands::take_for_heartbeat(&state, &hb.hostname).await;
+    if !queued.is_empty() {
+        tracing::info!("📬 Handing {} queued command(s) to {}", queued.len(), hb.hostname);
+        return Ok(Json(json!({ "status": "ok", "commands": queued })));
+    }
+
     Ok(Json(json!({ "status": "ok" })))
 }
 
@@ -35,6 +56,8 @@ pub async fn screenshot(
     Json(mut ss): Json<Screenshot>,
 ) -> Result<Json<Value>, StatusCode> {
     ss.timestamp = Utc::now();
+    let lesson = state.lesson_for(&ss.hostname);
+    ss.session_id = lesson.as_ref().map(|l| l.id.clone());
     let mut conn = state.redis.clone();
     let prefix = &state.config.key_prefix;
 
@@ -42,6 +65,10 @@ pub async fn screenshot(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    if let Some(lesson) = &lesson {
+        lessons::record_screenshot(&state, &lesson.id, &ss.hostname).await;
+    }
+
     Ok(Json(json!({ "status": "ok" })))
 }
 
@@ -51,6 +78,8 @@ pub async fn notification(
     Json(mut n): Json<Notification>,
 ) -> Result<Json<Value>, StatusCode> {
     n.timestamp = Utc::now();
+    let lesson = state.lesson_for(&n.hostname);
+    n.session_id = lesson.as_ref().map(|l| l.id.clone());
     let mut conn = state.redis.clone();
     let prefix = &state.config.key_prefix;
 
@@ -58,19 +87,20 @@ pub async fn notification(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    // Broadcast to teacher dashboard via WS
-    if let Some(teacher_tx) = state.ws_clients.get("teacher") {
-        let msg = serde_json::json!({
-            "type": "notification",
-            "hostname": n.hostname,
-            "title": n.title,
-            "message": n.message,
-            "level": n.level,
-            "timestamp": n.timestamp.to_rfc3339()
-        });
-        let _ = teacher_tx.send(axum::extract::ws::Message::Text(msg.to_string().into()));
+    if let Some(lesson) = &lesson {
+        lessons::record_notification(&state, &lesson.id, &n).await;
     }
 
+    // Broadcast to teacher dashboards via WS
+    state.notify_teachers(&serde_json::json!({
+        "type": "notification",
+        "hostname": n.hostname,
+        "title": n.title,
+        "message": n.message,
+        "level": n.level,
+        "timestamp": n.timestamp.to_rfc3339()
+    }));
+
     Ok(Json(json!({ "status": "ok" })))
 }
 
@@ -80,6 +110,8 @@ pub async fn apps(
     Json(mut app_list): Json<AppList>,
 ) -> Result<Json<Value>, StatusCode> {
     app_list.timestamp = Utc::now();
+    let lesson = state.lesson_for(&app_list.hostname);
+    app_list.session_id = lesson.as_ref().map(|l| l.id.clone());
     let mut conn = state.redis.clone();
     let prefix = &state.config.key_prefix;
 
@@ -87,6 +119,18 @@ pub async fn apps(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    if let Some(lesson) = &lesson {
+        lessons::record_apps(&state, &lesson.id, &app_list).await;
+    }
+
+    // Exam mode: anything outside the allowlist is flagged here, whatever
+    // the agent itself reports
+    let student_policy = policy::policy_for(&state, &mut conn, &app_list.hostname).await;
+    for v in exam::check(&state.exam_flags, &student_policy, &app_list) {
+        tracing::warn!("📝 Exam violation on {}: {} {}", v.hostname, v.rule, v.detail);
+        store_violation(&state, v).await?;
+    }
+
     Ok(Json(json!({ "status": "ok" })))
 }
 
@@ -96,25 +140,52 @@ pub async fn violation(
     Json(mut v): Json<Violation>,
 ) -> Result<Json<Value>, StatusCode> {
     v.timestamp = Utc::now();
+    v.session_id = state.lesson_for(&v.hostname).map(|l| l.id);
+    store_violation(&state, v).await?;
+
+    Ok(Json(json!({ "status": "ok" })))
+}
+
+/// Store a violation, add it to the running lesson and notify the teachers.
+async fn store_violation(state: &AppState, mut v: Violation) -> Result<(), StatusCode> {
     let mut conn = state.redis.clone();
     let prefix = &state.config.key_prefix;
 
+    // Attribute it to the roster student logged in (or seated) there;
+    // whatever identity the agent sent is not trusted
+    v.student_id = None;
+    v.student_name = None;
+    let username = redis_store::get_heartbeat(&mut conn, prefix, &v.hostname)
+        .await
+        .ok()
+        .flatten()
+        .map(|hb| hb.username)
+        .unwrap_or_default();
+    if let Some(student) = state.student_for(&v.hostname, &username) {
+        v.student_id = Some(student.id);
+        v.student_name = Some(student.name);
+    }
+
     redis_store::add_violation(&mut conn, prefix, &v)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    // Notify teacher via WS
-    if let Some(teacher_tx) = state.ws_clients.get("teacher") {
-        let msg = serde_json::json!({
-            "type": "violation",
-            "hostname": v.hostname,
-            "rule": v.rule,
-            "detail": v.detail,
-            "severity": v.severity,
-            "timestamp": v.timestamp.to_rfc3339()
-        });
-        let _ = teacher_tx.send(axum::extract::ws::Message::Text(msg.to_string().into()));
+    if let Some(lesson) = &v.session_id {
+        lessons::record_violation(state, lesson, &v).await;
     }
 
-    Ok(Json(json!({ "status": "ok" })))
+    // Notify teachers via WS
+    state.notify_teachers(&serde_json::json!({
+        "type": "violation",
+        "hostname": v.hostname,
+        "rule": v.rule,
+        "detail": v.detail,
+        "severity": v.severity,
+        "session_id": v.session_id,
+        "student_id": v.student_id,
+        "student_name": v.student_name,
+        "timestamp": v.timestamp.to_rfc3339()
+    }));
+
+    Ok(())
 }
@@ -0,0 +1,49 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::redis_store;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    /// RFC 3339 bounds, inclusive
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub hostname: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// GET /api/audit?from=&to=&actor=&action=&hostname=&limit=
+pub async fn audit_log(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<AuditQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let from_ms = q.from.map(|t| t.timestamp_millis()).unwrap_or(0);
+    let to_ms = q.to.map(|t| t.timestamp_millis()).unwrap_or(i64::MAX);
+
+    let mut entries = redis_store::get_audit_entries(&mut conn, prefix, from_ms, to_ms)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    entries.retain(|e| {
+        q.actor.as_ref().is_none_or(|a| &e.actor == a)
+            && q.action.as_ref().is_none_or(|a| &e.action == a)
+            && q.hostname.as_ref().is_none_or(|h| e.targets.contains(h))
+    });
+    entries.truncate(q.limit.unwrap_or(200));
+
+    Ok(Json(serde_json::json!({
+        "count": entries.len(),
+        "entries": entries,
+    })))
+}
@@ -0,0 +1,313 @@
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::audit;
+use crate::auth::{self, Identity, SESSION_COOKIE};
+use crate::models::{Role, TeacherAccount};
+use crate::redis_store;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTeacherRequest {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub role: Role,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeRoleRequest {
+    pub role: Role,
+}
+
+fn account_json(a: &TeacherAccount) -> Value {
+    json!({
+        "username": a.username,
+        "display_name": a.display_name,
+        "role": a.role,
+        "created_at": a.created_at.to_rfc3339(),
+    })
+}
+
+/// POST /api/auth/login
+/// Body: { "username": "...", "password": "..." }
+/// Returns a bearer token and sets the dashboard session cookie.
+/// Too many failures for the username or from the address answer 429
+/// until `login_lockout_secs` has passed.
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(body): Json<LoginRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let by_user = format!("user:{}", body.username.to_lowercase());
+    let by_ip = format!("ip:{}", peer.ip());
+    let user_failures = redis_store::get_login_failures(&mut conn, prefix, &by_user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let ip_failures = redis_store::get_login_failures(&mut conn, prefix, &by_ip)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if user_failures >= state.config.login_max_failures || ip_failures >= state.config.login_max_failures_per_ip {
+        tracing::warn!("🚫 Login for '{}' from {} refused: too many failures", body.username, peer.ip());
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    let account = redis_store::get_teacher(&mut conn, prefix, &body.username)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !auth::verify_login(&body.password, account.as_ref()) {
+        tracing::warn!("Failed login attempt for '{}' from {}", body.username, peer.ip());
+        let window = state.config.login_lockout_secs;
+        let _ = redis_store::add_login_failure(&mut conn, prefix, &by_user, window).await;
+        let _ = redis_store::add_login_failure(&mut conn, prefix, &by_ip, window).await;
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let Some(account) = account else { return Err(StatusCode::UNAUTHORIZED) };
+    let _ = redis_store::clear_login_failures(&mut conn, prefix, &by_user).await;
+
+    let token = auth::new_token();
+    let ttl = state.config.session_ttl_secs;
+    redis_store::store_auth_session(&mut conn, prefix, &token, &account.username, ttl)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tracing::info!("🔓 {} logged in", account.username);
+
+    let secure = if state.config.tls_enabled { "; Secure" } else { "" };
+    let cookie = format!(
+        "{SESSION_COOKIE}={token}; Path=/; HttpOnly; SameSite=Strict; Max-Age={ttl}{secure}"
+    );
+    Ok((
+        [(header::SET_COOKIE, cookie)],
+        Json(json!({
+            "token": token,
+            "expires_in": ttl,
+            "teacher": account_json(&account),
+        })),
+    ))
+}
+
+/// POST /api/auth/logout — revoke the current session
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    if let Some(token) = auth::session_token(&headers) {
+        let mut conn = state.redis.clone();
+        let prefix = &state.config.key_prefix;
+        let username = redis_store::get_auth_session(&mut conn, prefix, &token)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // An expired session has nothing left to delete
+        if let Some(username) = username {
+            redis_store::delete_auth_session(&mut conn, prefix, &username, &token)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+    }
+
+    let cookie = format!("{SESSION_COOKIE}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0");
+    Ok(([(header::SET_COOKIE, cookie)], Json(json!({ "status": "ok" }))))
+}
+
+/// GET /api/auth/me — the teacher behind the current session
+pub async fn me(Extension(identity): Extension<Identity>) -> Json<Value> {
+    Json(json!({
+        "username": identity.username,
+        "display_name": identity.display_name,
+        "role": identity.role,
+    }))
+}
+
+/// GET /api/teachers
+pub async fn list_teachers(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let mut teachers = redis_store::get_all_teachers(&mut conn, &state.config.key_prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    teachers.sort_by(|a, b| a.username.cmp(&b.username));
+
+    let list: Vec<Value> = teachers.iter().map(account_json).collect();
+    Ok(Json(json!({
+        "count": list.len(),
+        "teachers": list,
+    })))
+}
+
+/// POST /api/teachers
+/// Body: { "username": "...", "password": "...", "display_name": "...", "role": "teacher" }
+pub async fn create_teacher(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<CreateTeacherRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let username = body.username.trim().to_string();
+    if username.is_empty() || body.password.len() < 8 {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "Username is required and password must be at least 8 characters."
+        })));
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let existing = redis_store::get_teacher(&mut conn, prefix, &username)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if existing.is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let password_hash =
+        auth::hash_password(&body.password).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let account = TeacherAccount {
+        display_name: if body.display_name.is_empty() { username.clone() } else { body.display_name },
+        username,
+        password_hash,
+        role: body.role,
+        created_at: Utc::now(),
+    };
+    redis_store::store_teacher(&mut conn, prefix, &account)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let params = json!({ "username": account.username, "role": account.role });
+    audit::record(&state, &identity, "create_teacher", vec![], params, vec![]).await;
+    tracing::info!("👤 {} created teacher account '{}'", identity.username, account.username);
+    Ok(Json(account_json(&account)))
+}
+
+/// PUT /api/teachers/:username/password
+/// Body: { "password": "..." }
+/// Anyone may change their own password; admins may change anyone's.
+pub async fn change_password(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(username): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<ChangePasswordRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if identity.username != username && identity.role != Role::Admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if body.password.len() < 8 {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "Password must be at least 8 characters."
+        })));
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let mut account = redis_store::get_teacher(&mut conn, prefix, &username)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    account.password_hash =
+        auth::hash_password(&body.password).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    redis_store::store_teacher(&mut conn, prefix, &account)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Sign out everywhere else; someone changing their own password stays
+    // logged in on the session they did it from
+    let current = auth::session_token(&headers);
+    let keep = current.as_deref().filter(|_| identity.username == username);
+    let revoked = redis_store::revoke_auth_sessions(&mut conn, prefix, &username, keep)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let params = json!({ "username": username, "sessions_revoked": revoked });
+    audit::record(&state, &identity, "change_password", vec![], params, vec![]).await;
+    tracing::info!("👤 {} changed the password of '{username}'; {revoked} session(s) revoked", identity.username);
+    Ok(Json(json!({ "status": "ok", "sessions_revoked": revoked })))
+}
+
+/// PUT /api/teachers/:username/role
+/// Body: { "role": "observer" | "teacher" | "admin" }
+pub async fn change_role(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(username): Path<String>,
+    Json(body): Json<ChangeRoleRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if username == identity.username && body.role != Role::Admin {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "You cannot remove your own admin role."
+        })));
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let mut account = redis_store::get_teacher(&mut conn, prefix, &username)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    account.role = body.role;
+    redis_store::store_teacher(&mut conn, prefix, &account)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let params = json!({ "username": username, "role": account.role });
+    audit::record(&state, &identity, "change_role", vec![], params, vec![]).await;
+    tracing::info!("👤 {} set role of '{username}' to {:?}", identity.username, account.role);
+    Ok(Json(account_json(&account)))
+}
+
+/// DELETE /api/teachers/:username
+pub async fn delete_teacher(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(username): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    if username == identity.username {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "You cannot delete your own account."
+        })));
+    }
+
+    let mut conn = state.redis.clone();
+    let removed = redis_store::delete_teacher(&mut conn, &state.config.key_prefix, &username)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let _ = redis_store::revoke_auth_sessions(&mut conn, &state.config.key_prefix, &username, None).await;
+
+    let params = json!({ "username": username });
+    audit::record(&state, &identity, "delete_teacher", vec![], params, vec![]).await;
+    tracing::info!("👤 {} deleted teacher account '{username}'", identity.username);
+    Ok(Json(json!({ "status": "ok" })))
+}
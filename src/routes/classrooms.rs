@@ -0,0 +1,306 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::audit;
+use crate::auth::Identity;
+use crate::models::{AuditResult, BanPolicy, Classroom};
+use crate::policy;
+use crate::redis_store;
+use crate::routes::{students, violations};
+use crate::state::AppState;
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateClassroomRequest {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    pub banned_sites: Option<Vec<String>>,
+    pub banned_apps: Option<Vec<String>>,
+    pub sau_mode: Option<bool>,
+    pub exam_mode: Option<bool>,
+    pub allowed_sites: Option<Vec<String>>,
+    pub allowed_apps: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UpdateClassroomRequest {
+    pub name: Option<String>,
+    pub banned_sites: Option<Vec<String>>,
+    pub banned_apps: Option<Vec<String>>,
+    pub sau_mode: Option<bool>,
+    pub exam_mode: Option<bool>,
+    pub allowed_sites: Option<Vec<String>>,
+    pub allowed_apps: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AssignStudentsRequest {
+    pub hostnames: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ClassroomViolationQuery {
+    pub count: Option<isize>,
+}
+
+/// Room ids end up in Redis keys, so keep them to a safe alphabet.
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+async fn load_classroom(state: &AppState, id: &str) -> Result<Classroom, StatusCode> {
+    let mut conn = state.redis.clone();
+    redis_store::get_classroom(&mut conn, &state.config.key_prefix, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// GET /api/classrooms
+pub async fn list_classrooms(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let mut rooms = redis_store::get_all_classrooms(&mut conn, &state.config.key_prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    rooms.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let list: Vec<Value> = rooms
+        .iter()
+        .map(|room| {
+            let members = state
+                .student_rooms
+                .iter()
+                .filter(|e| e.value() == &room.id)
+                .count();
+            json!({ "classroom": room, "student_count": members })
+        })
+        .collect();
+    Ok(Json(json!({
+        "count": list.len(),
+        "classrooms": list,
+    })))
+}
+
+/// GET /api/classrooms/:id
+pub async fn get_classroom(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Classroom>, StatusCode> {
+    Ok(Json(load_classroom(&state, &id).await?))
+}
+
+/// POST /api/classrooms
+/// Body: { "id": "lab2", "name": "Lab 2", "banned_sites": [...], "banned_apps": [...], "sau_mode": false }
+/// Lists left out start as copies of the global ones.
+pub async fn create_classroom(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<CreateClassroomRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let id = body.id.trim().to_string();
+    if !valid_id(&id) {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "Classroom id must be 1-64 letters, digits, '-' or '_'."
+        })));
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let existing = redis_store::get_classroom(&mut conn, prefix, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if existing.is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let params = serde_json::to_value(&body).unwrap_or_default();
+    let global = state.base_policy();
+    let room = Classroom {
+        name: if body.name.is_empty() { id.clone() } else { body.name },
+        id,
+        policy: BanPolicy {
+            banned_sites: body.banned_sites.unwrap_or(global.banned_sites),
+            banned_apps: body.banned_apps.unwrap_or(global.banned_apps),
+            sau_mode: body.sau_mode.unwrap_or(global.sau_mode),
+            exam_mode: body.exam_mode.unwrap_or(global.exam_mode),
+            allowed_sites: body.allowed_sites.unwrap_or(global.allowed_sites),
+            allowed_apps: body.allowed_apps.unwrap_or(global.allowed_apps),
+        },
+        created_at: Utc::now(),
+    };
+
+    redis_store::store_classroom(&mut conn, prefix, &room)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    policy::publish(&mut conn, prefix, Some(&room.id), &room.policy)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&state, &identity, "create_classroom", vec![], params, vec![]).await;
+    tracing::info!("🏫 {} created classroom '{}'", identity.username, room.id);
+    Ok(Json(json!(room)))
+}
+
+/// PUT /api/classrooms/:id — rename or change the room's ban policy
+pub async fn update_classroom(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+    Json(body): Json<UpdateClassroomRequest>,
+) -> Result<Json<Classroom>, StatusCode> {
+    let params = serde_json::to_value(&body).unwrap_or_default();
+    let mut room = load_classroom(&state, &id).await?;
+
+    if let Some(name) = body.name {
+        room.name = name;
+    }
+    if let Some(sites) = body.banned_sites {
+        room.policy.banned_sites = sites;
+    }
+    if let Some(apps) = body.banned_apps {
+        room.policy.banned_apps = apps;
+    }
+    if let Some(sau) = body.sau_mode {
+        room.policy.sau_mode = sau;
+    }
+    if let Some(exam) = body.exam_mode {
+        room.policy.exam_mode = exam;
+    }
+    if let Some(sites) = body.allowed_sites {
+        room.policy.allowed_sites = sites;
+    }
+    if let Some(apps) = body.allowed_apps {
+        room.policy.allowed_apps = apps;
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    redis_store::store_classroom(&mut conn, prefix, &room)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    policy::publish(&mut conn, prefix, Some(&room.id), &room.policy)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&state, &identity, "update_classroom", vec![], params, vec![]).await;
+    tracing::info!("🏫 {} updated classroom '{id}' — {} apps, {} sites, SAU={}, exam={}",
+        identity.username, room.policy.banned_apps.len(), room.policy.banned_sites.len(),
+        room.policy.sau_mode, room.policy.exam_mode);
+    Ok(Json(room))
+}
+
+/// DELETE /api/classrooms/:id — members fall back to the global policy
+pub async fn delete_classroom(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let removed = redis_store::delete_classroom(&mut conn, &state.config.key_prefix, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state.student_rooms.retain(|_, room| room != &id);
+
+    let params = json!({ "id": id });
+    audit::record(&state, &identity, "delete_classroom", vec![], params, vec![]).await;
+    tracing::info!("🏫 {} deleted classroom '{id}'", identity.username);
+    Ok(Json(json!({ "status": "ok" })))
+}
+
+/// GET /api/classrooms/:id/students — the room's students, same shape as /api/students
+pub async fn classroom_students(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    load_classroom(&state, &id).await?;
+    let list = students::collect_students(&state, Some(&id), false).await?;
+
+    Ok(Json(json!({
+        "classroom": id,
+        "count": list.len(),
+        "students": list,
+    })))
+}
+
+/// PUT /api/classrooms/:id/students
+/// Body: { "hostnames": ["LAB2-PC01", "LAB2-PC02"] }
+/// Adds students to the room, moving them out of any other room.
+pub async fn assign_students(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+    Json(body): Json<AssignStudentsRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    load_classroom(&state, &id).await?;
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    let mut results = Vec::new();
+    for hostname in &body.hostnames {
+        match redis_store::assign_student(&mut conn, prefix, &id, hostname).await {
+            Ok(()) => {
+                state.student_rooms.insert(hostname.clone(), id.clone());
+                results.push(AuditResult::ok(hostname));
+            }
+            Err(e) => results.push(AuditResult::error(hostname, &e.to_string())),
+        }
+    }
+
+    let params = json!({ "classroom": id });
+    let targets = body.hostnames.clone();
+    audit::record(&state, &identity, "assign_classroom", targets, params, results.clone()).await;
+    tracing::info!("🏫 {} assigned {} student(s) to '{id}'", identity.username, body.hostnames.len());
+    Ok(Json(json!({
+        "classroom": id,
+        "results": results,
+    })))
+}
+
+/// DELETE /api/classrooms/:id/students/:hostname
+pub async fn unassign_student(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path((id, hostname)): Path<(String, String)>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let removed = redis_store::unassign_student(&mut conn, &state.config.key_prefix, &id, &hostname)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state.student_rooms.remove_if(&hostname, |_, room| room == &id);
+
+    let params = json!({ "classroom": id });
+    audit::record(&state, &identity, "unassign_classroom", vec![hostname], params, vec![]).await;
+    Ok(Json(json!({ "status": "ok" })))
+}
+
+/// GET /api/classrooms/:id/violations?count=50
+pub async fn classroom_violations(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(q): Query<ClassroomViolationQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    load_classroom(&state, &id).await?;
+    let all = violations::recent_violations(&state, Some(&id), q.count.unwrap_or(50)).await;
+
+    Ok(Json(json!({
+        "classroom": id,
+        "violations": all,
+    })))
+}
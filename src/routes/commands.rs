@@ -0,0 +1,85 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::commands;
+use crate::models::AgentCommand;
+use crate::redis_store;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct CommandQuery {
+    pub hostname: Option<String>,
+    /// pending | sending | delivered | acked | failed | expired
+    pub status: Option<String>,
+    pub count: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct CommandAck {
+    pub hostname: String,
+    pub id: String,
+    /// HTTP-style outcome of running the command
+    #[serde(default = "default_ack_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub body: Value,
+}
+
+fn default_ack_status() -> u16 {
+    200
+}
+
+/// GET /api/commands?hostname=<h>&status=pending&count=100 — newest first
+pub async fn list_commands(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<CommandQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let mut list: Vec<AgentCommand> = redis_store::get_all_commands(&mut conn, &state.config.key_prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter(|c| q.hostname.as_ref().is_none_or(|h| &c.hostname == h))
+        .filter(|c| q.status.as_ref().is_none_or(|s| &c.status == s))
+        .collect();
+    list.sort_by_key(|c| std::cmp::Reverse(c.created_at));
+    list.truncate(q.count.unwrap_or(100));
+
+    Ok(Json(json!({
+        "count": list.len(),
+        "commands": list,
+    })))
+}
+
+/// GET /api/commands/:id — status and outcome of one queued command
+pub async fn get_command(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<AgentCommand>, StatusCode> {
+    let mut conn = state.redis.clone();
+    redis_store::get_command(&mut conn, &state.config.key_prefix, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// POST /api/agent/command-ack
+/// Body: { "hostname": "...", "id": "...", "status": 200, "body": {...} }
+/// Answer to a command the agent received with its heartbeat.
+pub async fn command_ack(
+    State(state): State<Arc<AppState>>,
+    Json(ack): Json<CommandAck>,
+) -> Result<Json<Value>, StatusCode> {
+    let known = commands::ack(&state, &ack.hostname, &ack.id, ack.status, ack.body)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !known {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(json!({ "status": "ok" })))
+}
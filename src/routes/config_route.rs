@@ -1,64 +1,94 @@
 use axum::extract::State;
 use axum::http::StatusCode;
-use axum::Json;
-use serde::Deserialize;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::config::Config;
+use crate::audit;
+use crate::auth::Identity;
+use crate::config::{self, Config};
+use crate::policy;
 use crate::state::AppState;
 
+/// GET /api/config — settings with the ban lists as last saved
 pub async fn get_config(State(state): State<Arc<AppState>>) -> Json<Config> {
-    Json(state.config.clone())
+    let mut cfg = state.config.clone();
+    state.base_policy().apply_to(&mut cfg);
+    Json(cfg)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct UpdateConfigRequest {
     pub banned_sites: Option<Vec<String>>,
     pub banned_apps: Option<Vec<String>>,
     pub sau_mode: Option<bool>,
+    pub exam_mode: Option<bool>,
+    pub allowed_sites: Option<Vec<String>>,
+    pub allowed_apps: Option<Vec<String>>,
 }
 
 /// PUT /api/config — update banned lists and persist to config.toml + Redis
 pub async fn update_config(
     State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
     Json(body): Json<UpdateConfigRequest>,
 ) -> Result<Json<Config>, StatusCode> {
-    // Clone current config, apply changes
-    let mut cfg = state.config.clone();
+    let params = serde_json::to_value(&body).unwrap_or_default();
+
+    // Start from the lists as last saved, apply changes
+    let mut base = state.base_policy();
     if let Some(sites) = body.banned_sites {
-        cfg.banned_sites = sites;
+        base.banned_sites = sites;
     }
     if let Some(apps) = body.banned_apps {
-        cfg.banned_apps = apps;
+        base.banned_apps = apps;
     }
     if let Some(sau) = body.sau_mode {
-        cfg.sau_mode = sau;
+        base.sau_mode = sau;
+    }
+    if let Some(exam) = body.exam_mode {
+        base.exam_mode = exam;
+    }
+    if let Some(sites) = body.allowed_sites {
+        base.allowed_sites = sites;
+    }
+    if let Some(apps) = body.allowed_apps {
+        base.allowed_apps = apps;
     }
+    let mut cfg = state.config.clone();
+    base.apply_to(&mut cfg);
 
     // Persist to config.toml
-    let toml_str = toml::to_string_pretty(&cfg).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    std::fs::write("config.toml", &toml_str).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Err(e) = config::save_policy(&base) {
+        tracing::warn!("Could not save config.toml: {e}");
+        let params = serde_json::json!({ "changes": params, "error": e });
+        audit::record(&state, &identity, "update_config", vec![], params, vec![]).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    state.policy.write().unwrap().base = base;
 
-    // Publish to Redis so student agents can pick it up
+    // Publish to Redis so student agents can pick it up; a timetable
+    // profile in force keeps precedence until its slot ends
     let mut conn = state.redis.clone();
     let prefix = &state.config.key_prefix;
-    let ban_json = serde_json::json!({
-        "banned_processes": cfg.banned_apps,
-        "banned_domains": cfg.banned_sites,
-    });
-    let key = format!("{prefix}:ban_config");
-    let _: Result<(), _> = redis::AsyncCommands::set::<_, _, ()>(
-        &mut conn, &key, ban_json.to_string(),
-    ).await;
+    let _ = policy::publish(&mut conn, prefix, None, &state.effective_policy()).await;
 
-    // Publish SAU mode to Redis
-    let sau_key = format!("{prefix}:sau_mode");
-    let _: Result<(), _> = redis::AsyncCommands::set::<_, _, ()>(
-        &mut conn, &sau_key, if cfg.sau_mode { "1" } else { "0" },
-    ).await;
+    audit::record(&state, &identity, "update_config", vec![], params, vec![]).await;
 
-    tracing::info!("✅ Config updated & pushed to Redis — {} apps, {} sites, SAU={}",
-        cfg.banned_apps.len(), cfg.banned_sites.len(), cfg.sau_mode);
+    tracing::info!("✅ Config updated & pushed to Redis — {} apps, {} sites, SAU={}, exam={}",
+        cfg.banned_apps.len(), cfg.banned_sites.len(), cfg.sau_mode, cfg.exam_mode);
 
     Ok(Json(cfg))
 }
+
+/// GET /api/schedule — timetable, profiles and what is in force right now
+pub async fn get_schedule(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let current = state.policy.read().unwrap().clone();
+    Json(serde_json::json!({
+        "schedule": state.config.schedule,
+        "profiles": state.config.policy_profiles,
+        "active_profile": current.active_profile,
+        "effective": current.effective(&state.config),
+    }))
+}
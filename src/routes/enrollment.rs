@@ -0,0 +1,103 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::audit;
+use crate::auth::{self, Identity};
+use crate::models::AgentEnrollment;
+use crate::redis_store;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct EnrollRequest {
+    pub hostname: String,
+}
+
+/// POST /api/agents/enroll
+/// Body: { "hostname": "LAB2-PC07" }
+/// Issues (or rotates) the per-machine secret the agent signs its requests with.
+/// The secret is only ever returned here — copy it into the agent's config.
+pub async fn enroll_agent(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<EnrollRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let hostname = body.hostname.trim().to_string();
+    if hostname.is_empty() || hostname.contains('|') {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "Invalid hostname."
+        })));
+    }
+
+    let enrollment = AgentEnrollment {
+        hostname,
+        secret: auth::new_token(),
+        enrolled_by: identity.username.clone(),
+        enrolled_at: Utc::now(),
+    };
+
+    let mut conn = state.redis.clone();
+    redis_store::store_enrollment(&mut conn, &state.config.key_prefix, &enrollment)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let targets = vec![enrollment.hostname.clone()];
+    audit::record(&state, &identity, "enroll_agent", targets, json!({}), vec![]).await;
+    tracing::info!("🔑 {} enrolled agent {}", identity.username, enrollment.hostname);
+    Ok(Json(json!({
+        "hostname": enrollment.hostname,
+        "secret": enrollment.secret,
+        "enrolled_at": enrollment.enrolled_at.to_rfc3339(),
+    })))
+}
+
+/// GET /api/agents/enrolled — enrolled machines (secrets are not returned)
+pub async fn list_enrolled(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let mut enrolled = redis_store::get_all_enrollments(&mut conn, &state.config.key_prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    enrolled.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+
+    let list: Vec<Value> = enrolled
+        .iter()
+        .map(|e| json!({
+            "hostname": e.hostname,
+            "enrolled_by": e.enrolled_by,
+            "enrolled_at": e.enrolled_at.to_rfc3339(),
+        }))
+        .collect();
+    Ok(Json(json!({
+        "count": list.len(),
+        "agents": list,
+    })))
+}
+
+/// DELETE /api/agents/:hostname/enrollment — revoke a machine's secret and drop its connections
+pub async fn revoke_agent(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(hostname): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let removed = redis_store::delete_enrollment(&mut conn, &state.config.key_prefix, &hostname)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // Its signature no longer verifies, but connections it already has stay up
+    state.disconnect_agent(&hostname);
+
+    audit::record(&state, &identity, "revoke_agent", vec![hostname.clone()], json!({}), vec![]).await;
+    tracing::info!("🔑 {} revoked agent {hostname}", identity.username);
+    Ok(Json(json!({ "status": "ok" })))
+}
@@ -0,0 +1,206 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::audit;
+use crate::auth::Identity;
+use crate::models::StudentGroup;
+use crate::redis_store;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct CreateGroupRequest {
+    pub name: String,
+    #[serde(default)]
+    pub hostnames: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GroupMembersRequest {
+    pub hostnames: Vec<String>,
+}
+
+/// Group names end up in Redis keys and URLs, so keep them to a safe alphabet.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+async fn group_json(state: &AppState, group: &StudentGroup) -> Result<Value, StatusCode> {
+    let mut conn = state.redis.clone();
+    let mut members = redis_store::get_group_members(&mut conn, &state.config.key_prefix, &group.name)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    members.sort();
+
+    Ok(json!({
+        "name": group.name,
+        "created_by": group.created_by,
+        "created_at": group.created_at.to_rfc3339(),
+        "hostnames": members,
+    }))
+}
+
+async fn load_group(state: &AppState, name: &str) -> Result<StudentGroup, StatusCode> {
+    let mut conn = state.redis.clone();
+    redis_store::get_group(&mut conn, &state.config.key_prefix, name)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// GET /api/groups
+pub async fn list_groups(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let mut groups = redis_store::get_all_groups(&mut conn, &state.config.key_prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    groups.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut list = Vec::with_capacity(groups.len());
+    for group in &groups {
+        list.push(group_json(&state, group).await?);
+    }
+    Ok(Json(json!({
+        "count": list.len(),
+        "groups": list,
+    })))
+}
+
+/// GET /api/groups/:name
+pub async fn get_group(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let group = load_group(&state, &name).await?;
+    Ok(Json(group_json(&state, &group).await?))
+}
+
+/// POST /api/groups
+/// Body: { "name": "row-a", "hostnames": ["LAB2-PC01", "LAB2-PC02"] }
+pub async fn create_group(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<CreateGroupRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let name = body.name.trim().to_string();
+    if !valid_name(&name) {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "Group name must be 1-64 letters, digits, '-' or '_'."
+        })));
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let existing = redis_store::get_group(&mut conn, prefix, &name)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if existing.is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let group = StudentGroup {
+        name,
+        created_by: identity.username.clone(),
+        created_at: Utc::now(),
+    };
+    redis_store::store_group(&mut conn, prefix, &group)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    redis_store::set_group_members(&mut conn, prefix, &group.name, &body.hostnames)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let params = json!({ "group": group.name });
+    audit::record(&state, &identity, "create_group", body.hostnames, params, vec![]).await;
+    tracing::info!("👥 {} created group '{}'", identity.username, group.name);
+    Ok(Json(group_json(&state, &group).await?))
+}
+
+/// PUT /api/groups/:name/members
+/// Body: { "hostnames": [...] } — replaces the member list
+pub async fn set_members(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(name): Path<String>,
+    Json(body): Json<GroupMembersRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let group = load_group(&state, &name).await?;
+
+    let mut conn = state.redis.clone();
+    redis_store::set_group_members(&mut conn, &state.config.key_prefix, &name, &body.hostnames)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let params = json!({ "group": name });
+    audit::record(&state, &identity, "set_group_members", body.hostnames, params, vec![]).await;
+    Ok(Json(group_json(&state, &group).await?))
+}
+
+/// POST /api/groups/:name/members
+/// Body: { "hostnames": [...] } — adds to the member list
+pub async fn add_members(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(name): Path<String>,
+    Json(body): Json<GroupMembersRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let group = load_group(&state, &name).await?;
+
+    let mut conn = state.redis.clone();
+    redis_store::add_group_members(&mut conn, &state.config.key_prefix, &name, &body.hostnames)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let params = json!({ "group": name });
+    audit::record(&state, &identity, "add_group_members", body.hostnames, params, vec![]).await;
+    Ok(Json(group_json(&state, &group).await?))
+}
+
+/// DELETE /api/groups/:name/members/:hostname
+pub async fn remove_member(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path((name, hostname)): Path<(String, String)>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let removed = redis_store::remove_group_member(&mut conn, &state.config.key_prefix, &name, &hostname)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let params = json!({ "group": name });
+    audit::record(&state, &identity, "remove_group_member", vec![hostname], params, vec![]).await;
+    Ok(Json(json!({ "status": "ok" })))
+}
+
+/// DELETE /api/groups/:name
+pub async fn delete_group(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let removed = redis_store::delete_group(&mut conn, &state.config.key_prefix, &name)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let params = json!({ "group": name });
+    audit::record(&state, &identity, "delete_group", vec![], params, vec![]).await;
+    tracing::info!("👥 {} deleted group '{name}'", identity.username);
+    Ok(Json(json!({ "status": "ok" })))
+}
@@ -0,0 +1,108 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::audit;
+use crate::auth::Identity;
+use crate::layout::DEFAULT_ROOM;
+use crate::models::RoomLayout;
+use crate::redis_store;
+use crate::state::AppState;
+
+/// GET /api/layouts
+pub async fn list_layouts(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let mut layouts = redis_store::get_all_layouts(&mut conn, &state.config.key_prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    layouts.sort_by(|a, b| a.room.cmp(&b.room));
+
+    Ok(Json(json!({
+        "count": layouts.len(),
+        "layouts": layouts,
+    })))
+}
+
+/// GET /api/layouts/:room — `room` is a classroom id or "default"
+pub async fn get_layout(
+    State(state): State<Arc<AppState>>,
+    Path(room): Path<String>,
+) -> Result<Json<RoomLayout>, StatusCode> {
+    let mut conn = state.redis.clone();
+    redis_store::get_layout(&mut conn, &state.config.key_prefix, &room)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// PUT /api/layouts/:room
+/// Body: { "rows": 5, "cols": 6, "teacher_desk": { "row": 0, "col": 0 },
+///         "seats": [{ "hostname": "LAB2-PC07", "row": 1, "col": 2 }, ...] }
+/// Rows count from the front of the room, columns from the left.
+pub async fn put_layout(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(room): Path<String>,
+    Json(mut layout): Json<RoomLayout>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    if room != DEFAULT_ROOM
+        && redis_store::get_classroom(&mut conn, prefix, &room)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .is_none()
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    for a in &mut layout.seats {
+        a.hostname = a.hostname.trim().to_string();
+    }
+    if let Some(error) = layout.validate() {
+        return Ok(Json(json!({ "status": "error", "error": error })));
+    }
+
+    layout.room = room.clone();
+    layout.updated_by = identity.username.clone();
+    layout.updated_at = Utc::now();
+    redis_store::store_layout(&mut conn, prefix, &layout)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let params = json!({ "rows": layout.rows, "cols": layout.cols, "seats": layout.seats.len() });
+    audit::record(&state, &identity, "update_layout", vec![room.clone()], params, vec![]).await;
+    tracing::info!(
+        "🪑 {} saved layout for {room}: {}x{}, {} seats",
+        identity.username,
+        layout.rows,
+        layout.cols,
+        layout.seats.len()
+    );
+    Ok(Json(json!(layout)))
+}
+
+/// DELETE /api/layouts/:room
+pub async fn delete_layout(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(room): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let removed = redis_store::delete_layout(&mut conn, &state.config.key_prefix, &room)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    audit::record(&state, &identity, "delete_layout", vec![room.clone()], json!({}), vec![]).await;
+    tracing::info!("🪑 {} removed layout for {room}", identity.username);
+    Ok(Json(json!({ "status": "ok" })))
+}
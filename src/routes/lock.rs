@@ -1,29 +1,88 @@
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
-use axum::Json;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use reqwest::Method;
 use serde::Deserialize;
 use serde_json::Value;
 use std::sync::Arc;
 
-use crate::redis_store;
+use crate::agent_client::{self, Reply};
+use crate::audit;
+use crate::commands;
+use crate::conflicts;
+use crate::auth::Identity;
+use crate::fanout;
+use crate::lock_state;
+use crate::models::{AgentCommand, AuditResult};
 use crate::state::AppState;
+use crate::targets::{self, Target};
+
+/// Longest timed lock: a school day
+const MAX_LOCK_SECS: i64 = 12 * 3600;
 
 #[derive(Deserialize)]
 pub struct LockRequest {
     /// "soft" (minimize all) or "hard" (lock workstation)
     pub mode: String,
+    /// Unlock automatically after this many seconds
+    #[serde(default)]
+    pub duration_secs: Option<i64>,
+    /// Override a conflicting command from another teacher
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Deserialize, Default)]
+pub struct UnlockRequest {
+    /// Release a lock another teacher just set
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Deserialize, Default)]
+pub struct TargetedUnlockRequest {
+    #[serde(default)]
+    pub force: bool,
+    #[serde(flatten)]
+    pub target: Target,
 }
 
 #[derive(Deserialize)]
 pub struct OpenUrlRequest {
     pub url: String,
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Deserialize)]
+pub struct TargetedLockRequest {
+    pub mode: String,
+    #[serde(default)]
+    pub duration_secs: Option<i64>,
+    #[serde(default)]
+    pub force: bool,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+#[derive(Deserialize)]
+pub struct TargetedOpenUrlRequest {
+    pub url: String,
+    #[serde(default)]
+    pub force: bool,
+    #[serde(flatten)]
+    pub target: Target,
 }
 
 /// POST /api/students/:hostname/lock
-/// Body: { "mode": "soft" } or { "mode": "hard" }
+/// Body: { "mode": "soft" } or { "mode": "hard" }, plus "duration_secs": 300
+/// to unlock automatically and "force": true to override a different lock
+/// another teacher just sent.
 /// Forwards the lock command to the student agent's HTTP API.
 pub async fn lock_student(
     State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
     Path(hostname): Path<String>,
     Json(body): Json<LockRequest>,
 ) -> Result<Json<Value>, StatusCode> {
@@ -34,58 +93,123 @@ pub async fn lock_student(
             "error": "Invalid mode. Use 'soft' or 'hard'."
         })));
     }
+    let expires_at = match lock_expiry(body.duration_secs) {
+        Ok(at) => at,
+        Err(error) => return Ok(Json(serde_json::json!({ "status": "error", "error": error }))),
+    };
+    let hosts = [hostname.clone()];
+    if let Some(refused) = conflicts::guard(&state, &identity, &hosts, "lock", &body.mode, body.force) {
+        return Ok(Json(refused));
+    }
 
     // Find student's IP:port from Redis agent registry
     let mut conn = state.redis.clone();
     let prefix = &state.config.key_prefix;
 
-    let agents = redis_store::get_all_agents(&mut conn, prefix)
+    let params = serde_json::json!({ "mode": body.mode, "duration_secs": body.duration_secs });
+    let Some(agent) = agent_client::find_agent(&mut conn, prefix, &hostname)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    else {
+        let result = AuditResult::not_found(&hostname);
+        audit::record(&state, &identity, "lock", vec![hostname], params, vec![result]).await;
+        return Err(StatusCode::NOT_FOUND);
+    };
 
-    let entry = agents
-        .iter()
-        .find(|e| e.starts_with(&format!("{hostname}|")))
-        .ok_or(StatusCode::NOT_FOUND)?;
+    // Forward to student agent
+    let path = format!("/lock/{}", body.mode);
+    tracing::info!("🔒 Sending {} lock to {hostname}", body.mode);
 
-    let parts: Vec<&str> = entry.split('|').collect();
-    if parts.len() < 3 {
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    let (cmd, reply) = commands::submit(&state, &identity.username, &agent, Method::POST, &path, None).await;
+    let (response, result) = forward_result(&cmd, reply);
+    if result.status == "ok" {
+        tracing::info!("✅ Lock command accepted by {hostname}");
+    }
+    if sent(&result) {
+        conflicts::record(&state, &identity, &hosts, "lock", &body.mode);
     }
-    let ip = parts[1];
-    let port = parts[2];
+    let results = std::slice::from_ref(&result);
+    lock_state::record_lock(&state, &identity.username, &body.mode, expires_at, results).await;
 
-    // Forward to student agent
-    let url = format!("http://{ip}:{port}/lock/{}", body.mode);
-    tracing::info!("🔒 Sending {} lock to {hostname} at {url}", body.mode);
-
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    match client.post(&url).send().await {
-        Ok(resp) if resp.status().is_success() => {
-            let body: Value = resp.json().await.unwrap_or(serde_json::json!({"status": "ok"}));
-            tracing::info!("✅ Lock command accepted by {hostname}");
-            Ok(Json(body))
-        }
-        Ok(resp) => {
-            let status = resp.status();
+    audit::record(&state, &identity, "lock", vec![hostname], params, vec![result]).await;
+    Ok(Json(response))
+}
+
+/// POST /api/students/:hostname/unlock
+/// Body (optional): { "force": true } to release a lock another teacher just set.
+/// Releases a soft or hard lock and forgets the machine's lock state.
+pub async fn unlock_student(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(hostname): Path<String>,
+    body: Option<Json<UnlockRequest>>,
+) -> Result<Json<Value>, StatusCode> {
+    let Json(body) = body.unwrap_or_default();
+    let hosts = [hostname.clone()];
+    if let Some(refused) = conflicts::guard(&state, &identity, &hosts, "lock", "unlock", body.force) {
+        return Ok(Json(refused));
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let params = serde_json::json!({});
+    let Some(agent) = agent_client::find_agent(&mut conn, prefix, &hostname)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    else {
+        let result = AuditResult::not_found(&hostname);
+        audit::record(&state, &identity, "unlock", vec![hostname], params, vec![result]).await;
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    tracing::info!("🔓 Sending unlock to {hostname}");
+
+    let (cmd, reply) = commands::submit(&state, &identity.username, &agent, Method::POST, "/unlock", None).await;
+    let (response, result) = forward_result(&cmd, reply);
+    if result.status == "ok" {
+        tracing::info!("✅ Unlock accepted by {hostname}");
+    }
+    if sent(&result) {
+        conflicts::record(&state, &identity, &hosts, "lock", "unlock");
+    }
+    lock_state::record_unlock(&state, &identity.username, std::slice::from_ref(&result)).await;
+
+    audit::record(&state, &identity, "unlock", vec![hostname], params, vec![result]).await;
+    Ok(Json(response))
+}
+
+/// When a lock with `duration_secs` should be released, or why it can't be set
+fn lock_expiry(duration_secs: Option<i64>) -> Result<Option<DateTime<Utc>>, String> {
+    match duration_secs {
+        None => Ok(None),
+        Some(secs) if secs <= 0 => Err("duration_secs must be positive.".to_string()),
+        Some(secs) if secs > MAX_LOCK_SECS => Err(format!("duration_secs can be at most {MAX_LOCK_SECS}.")),
+        Some(secs) => Ok(Some(Utc::now() + chrono::Duration::seconds(secs))),
+    }
+}
+
+/// Turn an agent's reply into the JSON returned to the dashboard plus the
+/// per-host audit result. Unreachable students get the command queued.
+fn forward_result(cmd: &AgentCommand, reply: Reply) -> (Value, AuditResult) {
+    let hostname = cmd.hostname.as_str();
+    let result = fanout::result(cmd, &reply);
+    let response = match reply {
+        Reply::Ok(body) => body,
+        Reply::Status(status) => {
             tracing::warn!("Student {hostname} returned {status}");
-            Ok(Json(serde_json::json!({
-                "status": "error",
-                "error": format!("Student returned {status}")
-            })))
+            serde_json::json!({ "status": "error", "error": result.detail })
         }
-        Err(e) => {
+        Reply::Unreachable(e) if cmd.status == "pending" => {
+            tracing::warn!("Failed to reach student {hostname}: {e}; command {} queued", cmd.id);
+            serde_json::json!({ "status": "queued", "command_id": cmd.id, "detail": result.detail })
+        }
+        Reply::Unreachable(e) => {
             tracing::warn!("Failed to reach student {hostname}: {e}");
-            Ok(Json(serde_json::json!({
-                "status": "error",
-                "error": format!("Cannot reach student: {e}")
-            })))
+            serde_json::json!({ "status": "error", "error": result.detail })
         }
-    }
+    };
+    (response, result)
 }
 
 /// GET /api/apps/:hostname
@@ -97,67 +221,43 @@ pub async fn get_apps_student(
     let mut conn = state.redis.clone();
     let prefix = &state.config.key_prefix;
 
-    let agents = redis_store::get_all_agents(&mut conn, prefix)
+    let agent = agent_client::find_agent(&mut conn, prefix, &hostname)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let entry = agents
-        .iter()
-        .find(|e| e.starts_with(&format!("{hostname}|")))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    let parts: Vec<&str> = entry.split('|').collect();
-    if parts.len() < 3 {
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-    let ip = parts[1];
-    let port = parts[2];
-
-    let url = format!("http://{ip}:{port}/apps");
-    tracing::info!("📋 Fetching apps from {hostname} at {url}");
-
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    match client.get(&url).send().await {
-        Ok(resp) if resp.status().is_success() => {
-            let body: Value = resp.json().await.unwrap_or(serde_json::json!({
-                "hostname": hostname,
-                "applications": [],
-                "browser_tabs": [],
-            }));
-            Ok(Json(body))
-        }
-        Ok(resp) => {
-            let status = resp.status();
+    tracing::info!("📋 Fetching apps from {hostname}");
+
+    let empty = serde_json::json!({
+        "hostname": hostname,
+        "applications": [],
+        "browser_tabs": [],
+    });
+    match agent_client::send_command(&state, Method::GET, &agent, "/apps", None).await {
+        Reply::Ok(body) => Ok(Json(body)),
+        Reply::Status(status) => {
             tracing::warn!("Student {hostname} returned {status}");
-            Ok(Json(serde_json::json!({
-                "hostname": hostname,
-                "applications": [],
-                "browser_tabs": [],
-                "error": format!("Student returned {status}")
-            })))
+            let mut body = empty;
+            body["error"] = format!("Student returned {status}").into();
+            Ok(Json(body))
         }
-        Err(e) => {
+        Reply::Unreachable(e) => {
             tracing::warn!("Failed to reach student {hostname}: {e}");
-            Ok(Json(serde_json::json!({
-                "hostname": hostname,
-                "applications": [],
-                "browser_tabs": [],
-                "error": format!("Cannot reach student: {e}")
-            })))
+            let mut body = empty;
+            body["error"] = format!("Cannot reach student: {e}").into();
+            Ok(Json(body))
         }
     }
 }
 
 /// POST /api/broadcast/open-url
-/// Body: { "url": "https://kahoot.it/..." }
-/// Opens a URL on ALL active student PCs.
+/// Body: { "url": "https://kahoot.it/..." }, optionally narrowed with a
+/// "group", "hostnames" or "classroom" target.
+/// Opens a URL on every online student PC.
 pub async fn broadcast_open_url(
     State(state): State<Arc<AppState>>,
-    Json(body): Json<OpenUrlRequest>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<TargetedOpenUrlRequest>,
 ) -> Result<Json<Value>, StatusCode> {
     if !body.url.starts_with("http://") && !body.url.starts_with("https://") {
         return Ok(Json(serde_json::json!({
@@ -166,52 +266,255 @@ pub async fn broadcast_open_url(
         })));
     }
 
-    let mut conn = state.redis.clone();
-    let prefix = &state.config.key_prefix;
+    let resolved = targets::resolve(&state, &body.target).await?;
+    let hosts = resolved.hostnames();
+    if let Some(refused) = conflicts::guard(&state, &identity, &hosts, "open_url", &body.url, body.force) {
+        return Ok(Json(refused));
+    }
+    let payload = serde_json::json!({ "url": body.url });
+    let params = serde_json::json!({ "url": body.url, "target": body.target });
+    let results = fanout::send(&state, &identity.username, &resolved, Method::POST, "/open-url", Some(&payload)).await;
+    conflicts::record(&state, &identity, &sent_to(&results), "open_url", &body.url);
 
-    let agents = redis_store::get_all_agents(&mut conn, prefix)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let mut success = 0usize;
-    let mut failed = 0usize;
-    let total = agents.len();
-
-    for entry in &agents {
-        let parts: Vec<&str> = entry.split('|').collect();
-        if parts.len() < 3 { failed += 1; continue; }
-        let ip = parts[1];
-        let port = parts[2];
-        let hostname = parts[0];
-
-        let url = format!("http://{ip}:{port}/open-url");
-        match client
-            .post(&url)
-            .json(&serde_json::json!({ "url": body.url }))
-            .send()
-            .await
-        {
-            Ok(resp) if resp.status().is_success() => {
-                tracing::info!("✅ URL opened on {hostname}");
-                success += 1;
-            }
-            _ => {
-                tracing::warn!("❌ Failed to open URL on {hostname}");
-                failed += 1;
-            }
-        }
+    audit_results(&state, &identity, "broadcast_open_url", params, results).await
+}
+
+/// POST /api/broadcast/lock
+/// Body: { "mode": "soft" } or { "mode": "hard" }, optionally narrowed with a
+/// "group", "hostnames" or "classroom" target, with "duration_secs" and "force"
+/// as for a single student.
+/// Locks every online student PC (see targets.rs).
+pub async fn broadcast_lock(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<TargetedLockRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let action = "broadcast_lock";
+    lock_many(&state, &identity, action, &body.mode, body.duration_secs, body.target, body.force).await
+}
+
+/// POST /api/broadcast/unlock
+/// Body (optional): a "group", "hostnames" or "classroom" target, and "force".
+/// Unlocks every online student PC and every locked one; offline ones
+/// get the unlock queued.
+pub async fn broadcast_unlock(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    body: Option<Json<TargetedUnlockRequest>>,
+) -> Result<Json<Value>, StatusCode> {
+    let Json(body) = body.unwrap_or_default();
+    unlock_many(&state, &identity, "broadcast_unlock", body.target, body.force).await
+}
+
+/// POST /api/students/lock
+/// Body: { "mode": "soft", "hostnames": [...] } or { "mode": "soft", "group": "row-a" }
+/// "duration_secs": 300 unlocks them again after five minutes.
+pub async fn lock_targets(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<TargetedLockRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    // Selected hosts only; "everyone" goes through the broadcast endpoints
+    if body.target.is_empty() {
+        return Ok(Json(serde_json::json!({
+            "status": "error",
+            "error": "Specify a group, hostnames or classroom."
+        })));
     }
+    lock_many(&state, &identity, "lock", &body.mode, body.duration_secs, body.target, body.force).await
+}
+
+/// POST /api/groups/:name/lock
+/// Body: { "mode": "soft" } or { "mode": "hard" }, optionally with "duration_secs"
+pub async fn lock_group(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(name): Path<String>,
+    Json(body): Json<LockRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let target = Target::group(&name);
+    lock_many(&state, &identity, "lock", &body.mode, body.duration_secs, target, body.force).await
+}
+
+/// POST /api/students/unlock
+/// Body: { "hostnames": [...] } or { "group": "row-a" }, plus "force": true
+/// to release locks another teacher just set.
+pub async fn unlock_targets(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<TargetedUnlockRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    // Selected hosts only; "everyone" goes through the broadcast endpoints
+    if body.target.is_empty() {
+        return Ok(Json(serde_json::json!({
+            "status": "error",
+            "error": "Specify a group, hostnames or classroom."
+        })));
+    }
+    unlock_many(&state, &identity, "unlock", body.target, body.force).await
+}
+
+/// POST /api/groups/:name/unlock
+/// Body (optional): { "force": true }
+pub async fn unlock_group(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(name): Path<String>,
+    body: Option<Json<UnlockRequest>>,
+) -> Result<Json<Value>, StatusCode> {
+    let Json(body) = body.unwrap_or_default();
+    unlock_many(&state, &identity, "unlock", Target::group(&name), body.force).await
+}
+
+/// POST /api/students/open-url
+/// Body: { "url": "https://...", "hostnames": [...] } or { "url": "https://...", "group": "row-a" }
+pub async fn open_url_targets(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<TargetedOpenUrlRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    open_url_many(&state, &identity, &body.url, body.target, body.force).await
+}
+
+/// POST /api/groups/:name/open-url
+/// Body: { "url": "https://kahoot.it/..." }
+pub async fn open_url_group(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(name): Path<String>,
+    Json(body): Json<OpenUrlRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    open_url_many(&state, &identity, &body.url, Target::group(&name), body.force).await
+}
+
+async fn lock_many(
+    state: &Arc<AppState>,
+    identity: &Identity,
+    action: &str,
+    mode: &str,
+    duration_secs: Option<i64>,
+    target: Target,
+    force: bool,
+) -> Result<Json<Value>, StatusCode> {
+    if mode != "soft" && mode != "hard" {
+        return Ok(Json(serde_json::json!({
+            "status": "error",
+            "error": "Invalid mode. Use 'soft' or 'hard'."
+        })));
+    }
+    let expires_at = match lock_expiry(duration_secs) {
+        Ok(at) => at,
+        Err(error) => return Ok(Json(serde_json::json!({ "status": "error", "error": error }))),
+    };
+
+    let resolved = targets::resolve(state, &target).await?;
+    if let Some(refused) = conflicts::guard(state, identity, &resolved.hostnames(), "lock", mode, force) {
+        return Ok(Json(refused));
+    }
+    tracing::info!("🔒 Sending {mode} lock to {} student(s)", resolved.agents.len());
+
+    let path = format!("/lock/{mode}");
+    let params = serde_json::json!({ "mode": mode, "duration_secs": duration_secs, "target": target });
+    let results = fanout::send(state, &identity.username, &resolved, Method::POST, &path, None).await;
+    conflicts::record(state, identity, &sent_to(&results), "lock", mode);
+    lock_state::record_lock(state, &identity.username, mode, expires_at, &results).await;
+
+    audit_results(state, identity, action, params, results).await
+}
+
+async fn unlock_many(
+    state: &Arc<AppState>,
+    identity: &Identity,
+    action: &str,
+    target: Target,
+    force: bool,
+) -> Result<Json<Value>, StatusCode> {
+    let resolved = targets::resolve_unlock(state, &target).await?;
+    if let Some(refused) = conflicts::guard(state, identity, &resolved.hostnames(), "lock", "unlock", force) {
+        return Ok(Json(refused));
+    }
+    tracing::info!("🔓 Sending unlock to {} student(s)", resolved.agents.len());
+
+    let params = serde_json::json!({ "target": target });
+    let results = fanout::send(state, &identity.username, &resolved, Method::POST, "/unlock", None).await;
+    conflicts::record(state, identity, &sent_to(&results), "lock", "unlock");
+    lock_state::record_unlock(state, &identity.username, &results).await;
+
+    audit_results(state, identity, action, params, results).await
+}
+
+async fn open_url_many(
+    state: &Arc<AppState>,
+    identity: &Identity,
+    url: &str,
+    target: Target,
+    force: bool,
+) -> Result<Json<Value>, StatusCode> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Ok(Json(serde_json::json!({
+            "status": "error",
+            "error": "URL must start with http:// or https://"
+        })));
+    }
+    if target.is_empty() {
+        return Ok(Json(serde_json::json!({
+            "status": "error",
+            "error": "Specify a group, hostnames or classroom."
+        })));
+    }
+
+    let resolved = targets::resolve(state, &target).await?;
+    if let Some(refused) = conflicts::guard(state, identity, &resolved.hostnames(), "open_url", url, force) {
+        return Ok(Json(refused));
+    }
+    tracing::info!("🌐 Opening URL on {} student(s): {url}", resolved.agents.len());
+
+    let payload = serde_json::json!({ "url": url });
+    let params = serde_json::json!({ "url": url, "target": target });
+    let results = fanout::send(state, &identity.username, &resolved, Method::POST, "/open-url", Some(&payload)).await;
+    conflicts::record(state, identity, &sent_to(&results), "open_url", url);
+
+    audit_results(state, identity, "open_url", params, results).await
+}
+
+/// Whether a command reached the host or is still queued for it, and so
+/// counts against later commands from other teachers
+fn sent(result: &AuditResult) -> bool {
+    result.status == "ok" || lock_state::queued_command(result).is_some()
+}
+
+/// Hosts a multi-host command reached or is still queued for
+fn sent_to(results: &[AuditResult]) -> Vec<String> {
+    results.iter().filter(|r| sent(r)).map(|r| r.hostname.clone()).collect()
+}
+
+/// Record a multi-host command and summarise it for the dashboard.
+async fn audit_results(
+    state: &AppState,
+    identity: &Identity,
+    action: &str,
+    params: Value,
+    results: Vec<AuditResult>,
+) -> Result<Json<Value>, StatusCode> {
+    let count = |status: &str| results.iter().filter(|r| r.status == status).count();
+    let total = results.len();
+    let success = count("ok");
+    let unreachable = count("unreachable");
+    let skipped_offline = count("skipped_offline");
+    let timeout = count("timeout");
+
+    let targets = results.iter().map(|r| r.hostname.clone()).collect();
+    audit::record(state, identity, action, targets, params, results.clone()).await;
 
     Ok(Json(serde_json::json!({
         "status": "ok",
         "total": total,
         "success": success,
-        "failed": failed,
+        "unreachable": unreachable,
+        "skipped_offline": skipped_offline,
+        "timeout": timeout,
+        "failed": total - success - unreachable - skipped_offline - timeout,
+        "results": results,
     })))
 }
 
@@ -220,6 +523,7 @@ pub async fn broadcast_open_url(
 /// Opens a URL in the student's default browser.
 pub async fn open_url_student(
     State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
     Path(hostname): Path<String>,
     Json(body): Json<OpenUrlRequest>,
 ) -> Result<Json<Value>, StatusCode> {
@@ -229,60 +533,37 @@ pub async fn open_url_student(
             "error": "URL must start with http:// or https://"
         })));
     }
+    let hosts = [hostname.clone()];
+    if let Some(refused) = conflicts::guard(&state, &identity, &hosts, "open_url", &body.url, body.force) {
+        return Ok(Json(refused));
+    }
 
     // Find student's IP:port from Redis agent registry
     let mut conn = state.redis.clone();
     let prefix = &state.config.key_prefix;
 
-    let agents = redis_store::get_all_agents(&mut conn, prefix)
+    let params = serde_json::json!({ "url": body.url });
+    let Some(agent) = agent_client::find_agent(&mut conn, prefix, &hostname)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    else {
+        let result = AuditResult::not_found(&hostname);
+        audit::record(&state, &identity, "open_url", vec![hostname], params, vec![result]).await;
+        return Err(StatusCode::NOT_FOUND);
+    };
 
-    let entry = agents
-        .iter()
-        .find(|e| e.starts_with(&format!("{hostname}|")))
-        .ok_or(StatusCode::NOT_FOUND)?;
-
-    let parts: Vec<&str> = entry.split('|').collect();
-    if parts.len() < 3 {
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-    let ip = parts[1];
-    let port = parts[2];
-
-    let url = format!("http://{ip}:{port}/open-url");
     tracing::info!("🌐 Opening URL on {hostname}: {}", body.url);
 
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    match client
-        .post(&url)
-        .json(&serde_json::json!({ "url": body.url }))
-        .send()
-        .await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            let body: Value = resp.json().await.unwrap_or(serde_json::json!({"status": "ok"}));
-            tracing::info!("✅ URL opened on {hostname}");
-            Ok(Json(body))
-        }
-        Ok(resp) => {
-            let status = resp.status();
-            tracing::warn!("Student {hostname} returned {status}");
-            Ok(Json(serde_json::json!({
-                "status": "error",
-                "error": format!("Student returned {status}")
-            })))
-        }
-        Err(e) => {
-            tracing::warn!("Failed to reach student {hostname}: {e}");
-            Ok(Json(serde_json::json!({
-                "status": "error",
-                "error": format!("Cannot reach student: {e}")
-            })))
-        }
+    let (cmd, reply) =
+        commands::submit(&state, &identity.username, &agent, Method::POST, "/open-url", Some(&params)).await;
+    let (response, result) = forward_result(&cmd, reply);
+    if result.status == "ok" {
+        tracing::info!("✅ URL opened on {hostname}");
     }
+    if sent(&result) {
+        conflicts::record(&state, &identity, &hosts, "open_url", &body.url);
+    }
+
+    audit::record(&state, &identity, "open_url", vec![hostname], params, vec![result]).await;
+    Ok(Json(response))
 }
@@ -1,9 +1,22 @@
+pub mod access_requests;
 pub mod agent;
+pub mod audit;
+pub mod auth;
+pub mod classrooms;
+pub mod commands;
 pub mod config_route;
+pub mod enrollment;
+pub mod groups;
 pub mod health;
 pub mod info;
+pub mod layouts;
 pub mod lock;
+pub mod overrides;
+pub mod presence;
+pub mod roster;
 pub mod screen_ws;
+pub mod sessions;
 pub mod students;
+pub mod throttle;
 pub mod violations;
 pub mod ws;
@@ -0,0 +1,170 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::audit;
+use crate::auth::{self, Identity};
+use crate::models::PolicyOverride;
+use crate::overrides;
+use crate::redis_store;
+use crate::state::AppState;
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateOverrideRequest {
+    pub hostname: Option<String>,
+    pub username: Option<String>,
+    #[serde(default)]
+    pub allow_sites: Vec<String>,
+    #[serde(default)]
+    pub allow_apps: Vec<String>,
+    #[serde(default)]
+    pub ban_sites: Vec<String>,
+    #[serde(default)]
+    pub ban_apps: Vec<String>,
+    #[serde(default)]
+    pub reason: String,
+    /// Lifetime from now; takes precedence over `expires_at`
+    pub expires_in_secs: Option<i64>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct OverrideQuery {
+    pub hostname: Option<String>,
+    pub username: Option<String>,
+}
+
+/// GET /api/overrides?hostname=<h>&username=<u> — live overrides
+pub async fn list_overrides(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<OverrideQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let now = Utc::now();
+    let mut list: Vec<PolicyOverride> = redis_store::get_all_overrides(&mut conn, &state.config.key_prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter(|o| !o.is_expired(now))
+        .filter(|o| q.hostname.is_none() || o.hostname == q.hostname)
+        .filter(|o| q.username.is_none() || o.username == q.username)
+        .collect();
+    list.sort_by_key(|o| o.created_at);
+
+    Ok(Json(json!({
+        "count": list.len(),
+        "overrides": list,
+    })))
+}
+
+/// POST /api/overrides
+/// Body: { "hostname": "LAB2-PC07", "allow_sites": ["youtube.com"],
+///         "reason": "presentation", "expires_in_secs": 2700 }
+/// Target either a hostname or a username, not both.
+pub async fn create_override(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<CreateOverrideRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if body.hostname.is_some() == body.username.is_some() {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "Give either a hostname or a username."
+        })));
+    }
+    if body.allow_sites.is_empty()
+        && body.allow_apps.is_empty()
+        && body.ban_sites.is_empty()
+        && body.ban_apps.is_empty()
+    {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "An override needs at least one site or app."
+        })));
+    }
+
+    // Every override runs out, at the latest after `override_max_secs`
+    let now = Utc::now();
+    let max_secs = state.config.override_max_secs;
+    let latest = chrono::Duration::try_seconds(max_secs).and_then(|d| now.checked_add_signed(d));
+    let expires_at = match body.expires_in_secs {
+        Some(secs) if secs > 0 && secs <= max_secs => {
+            chrono::Duration::try_seconds(secs).and_then(|d| now.checked_add_signed(d))
+        }
+        Some(_) => {
+            return Ok(Json(json!({
+                "status": "error",
+                "error": format!("expires_in_secs must be between 1 and {max_secs}.")
+            })));
+        }
+        None => body.expires_at.or(latest),
+    };
+    let Some(expires_at) = expires_at else {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+    if expires_at <= now {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "Expiry is in the past."
+        })));
+    }
+    if latest.is_some_and(|latest| expires_at > latest) {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": format!("An override can last at most {max_secs} seconds.")
+        })));
+    }
+
+    let params = serde_json::to_value(&body).unwrap_or_default();
+    let entry = PolicyOverride {
+        id: auth::new_token()[..12].to_string(),
+        hostname: body.hostname,
+        username: body.username,
+        allow_sites: body.allow_sites,
+        allow_apps: body.allow_apps,
+        ban_sites: body.ban_sites,
+        ban_apps: body.ban_apps,
+        reason: body.reason,
+        created_by: identity.username.clone(),
+        created_at: now,
+        expires_at: Some(expires_at),
+    };
+
+    overrides::create(&state, &entry)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let subject = entry.subject().unwrap_or_default();
+
+    audit::record(&state, &identity, "create_override", vec![subject.clone()], params, vec![]).await;
+    tracing::info!("🎟️ {} added override {} for {subject}", identity.username, entry.id);
+    Ok(Json(json!(entry)))
+}
+
+/// DELETE /api/overrides/:id
+pub async fn delete_override(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let entry = redis_store::get_override(&mut conn, prefix, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    redis_store::delete_override(&mut conn, prefix, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let subject = entry.subject().unwrap_or_default();
+    overrides::publish_subject(&state, &mut conn, &subject).await;
+
+    let params = json!({ "id": id });
+    audit::record(&state, &identity, "delete_override", vec![subject.clone()], params, vec![]).await;
+    tracing::info!("🎟️ {} removed override {id} for {subject}", identity.username);
+    Ok(Json(json!({ "status": "ok" })))
+}
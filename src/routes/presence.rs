@@ -0,0 +1,44 @@
+use axum::extract::State;
+use axum::Json;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::state::AppState;
+
+/// Connected teacher dashboards and the student each one has open, oldest first
+fn snapshot(state: &AppState) -> Vec<Value> {
+    let mut sessions: Vec<_> = state
+        .teachers
+        .iter()
+        .map(|t| (t.connected_at, t.key().clone(), t.username.clone(), t.viewing.clone()))
+        .collect();
+    sessions.sort();
+    sessions
+        .into_iter()
+        .map(|(connected_at, session, username, viewing)| {
+            json!({
+                "session": session,
+                "username": username,
+                "connected_at": connected_at.to_rfc3339(),
+                "viewing": viewing,
+            })
+        })
+        .collect()
+}
+
+/// Push the current presence list to every dashboard.
+pub fn announce(state: &AppState) {
+    state.notify_dashboards(&json!({
+        "type": "presence",
+        "teachers": snapshot(state),
+    }));
+}
+
+/// GET /api/presence — who is connected and what they are looking at
+pub async fn presence(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let teachers = snapshot(&state);
+    Json(json!({
+        "count": teachers.len(),
+        "teachers": teachers,
+    }))
+}
@@ -0,0 +1,221 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::audit;
+use crate::auth::Identity;
+use crate::csv;
+use crate::models::RosterStudent;
+use crate::redis_store;
+use crate::roster::{self, non_empty};
+use crate::routes::violations::recent_violations;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// Drop every existing entry before importing
+    #[serde(default)]
+    pub replace: bool,
+}
+
+#[derive(Deserialize)]
+pub struct AssignmentRequest {
+    /// Seat the student at this machine; "" clears, omitted keeps
+    pub hostname: Option<String>,
+    /// Personal login to recognise the student by; "" clears, omitted keeps
+    pub username: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub classroom: Option<String>,
+    pub count: Option<isize>,
+}
+
+/// GET /api/roster — all students, by name
+pub async fn list_roster(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let mut students: Vec<RosterStudent> = state.roster.iter().map(|s| s.clone()).collect();
+    students.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+
+    Json(json!({
+        "count": students.len(),
+        "students": students,
+    }))
+}
+
+/// POST /api/roster
+/// Body: { "id": "20231042", "name": "Jane Doe", "email": "...", "username": "jdoe", "hostname": "LAB2-PC07" }
+/// Adds a student or replaces the entry with the same id.
+pub async fn upsert_student(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<RosterStudent>,
+) -> Result<Json<Value>, StatusCode> {
+    let (Some(id), Some(name)) = (non_empty(&body.id), non_empty(&body.name)) else {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": "id and name are required."
+        })));
+    };
+    let student = RosterStudent {
+        id,
+        name,
+        email: body.email.trim().to_string(),
+        username: body.username.as_deref().and_then(non_empty),
+        hostname: body.hostname.as_deref().and_then(non_empty),
+    };
+
+    roster::save(&state, student.clone())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let params = serde_json::to_value(&student).unwrap_or_default();
+    audit::record(&state, &identity, "upsert_roster_student", vec![student.id.clone()], params, vec![]).await;
+    tracing::info!("🧑‍🎓 {} saved roster entry {} ({})", identity.username, student.id, student.name);
+    Ok(Json(json!(student)))
+}
+
+/// POST /api/roster/import?replace=true
+/// Body: CSV text with a header line, e.g.
+///   id,name,email,username,hostname
+///   20231042,Jane Doe,jane@school.edu,jdoe,LAB2-PC07
+/// Existing students keep their login and seat unless the file sets them.
+pub async fn import_roster(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Query(q): Query<ImportQuery>,
+    body: String,
+) -> Result<Json<Value>, StatusCode> {
+    let (students, errors) = match roster::parse_import(&body) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(Json(json!({ "status": "error", "error": e }))),
+    };
+
+    if q.replace {
+        let mut conn = state.redis.clone();
+        redis_store::clear_roster(&mut conn, &state.config.key_prefix)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        state.roster.clear();
+    }
+
+    let imported = students.len();
+    for mut student in students {
+        if let Some(existing) = state.roster.get(&student.id).map(|s| s.clone()) {
+            student.username = student.username.or(existing.username);
+            student.hostname = student.hostname.or(existing.hostname);
+            if student.email.is_empty() {
+                student.email = existing.email;
+            }
+        }
+        roster::save(&state, student)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    let params = json!({ "replace": q.replace, "imported": imported, "rejected": errors.len() });
+    audit::record(&state, &identity, "import_roster", vec![], params, vec![]).await;
+    tracing::info!("🧑‍🎓 {} imported {imported} roster entries ({} rejected)", identity.username, errors.len());
+    Ok(Json(json!({
+        "status": "ok",
+        "imported": imported,
+        "errors": errors,
+    })))
+}
+
+/// PUT /api/roster/:id/assignment
+/// Body: { "hostname": "LAB2-PC07" } and/or { "username": "jdoe" }
+pub async fn assign_student(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+    Json(body): Json<AssignmentRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut student = state.roster.get(&id).map(|s| s.clone()).ok_or(StatusCode::NOT_FOUND)?;
+    if let Some(hostname) = &body.hostname {
+        student.hostname = non_empty(hostname);
+    }
+    if let Some(username) = &body.username {
+        student.username = non_empty(username);
+    }
+
+    roster::save(&state, student.clone())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let params = json!({ "hostname": student.hostname, "username": student.username });
+    audit::record(&state, &identity, "assign_roster_student", vec![id.clone()], params, vec![]).await;
+    tracing::info!(
+        "🪑 {} seated {id} at {} (login {})",
+        identity.username,
+        student.hostname.as_deref().unwrap_or("-"),
+        student.username.as_deref().unwrap_or("-")
+    );
+    Ok(Json(json!(student)))
+}
+
+/// DELETE /api/roster/:id
+pub async fn delete_student(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let removed = redis_store::delete_roster_student(&mut conn, &state.config.key_prefix, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state.roster.remove(&id);
+
+    audit::record(&state, &identity, "delete_roster_student", vec![id.clone()], json!({}), vec![]).await;
+    tracing::info!("🧑‍🎓 {} removed roster entry {id}", identity.username);
+    Ok(Json(json!({ "status": "ok" })))
+}
+
+/// GET /api/violations/export?classroom=<id>&count=500 — CSV download
+/// Violations recorded before a student was mapped leave the student
+/// columns empty; whoever sits at the machine now may not be who broke
+/// the rule.
+pub async fn export_violations(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<ExportQuery>,
+) -> impl IntoResponse {
+    let violations = recent_violations(&state, q.classroom.as_deref(), q.count.unwrap_or(500)).await;
+
+    let mut out = String::new();
+    csv::write_row(
+        &mut out,
+        &["timestamp", "hostname", "student_id", "student_name", "rule", "detail", "severity", "session_id"],
+    );
+    for v in &violations {
+        let student_id = v.student_id.clone().unwrap_or_default();
+        let student_name = v.student_name.clone().unwrap_or_default();
+        csv::write_row(
+            &mut out,
+            &[
+                v.timestamp.to_rfc3339(),
+                v.hostname.clone(),
+                student_id,
+                student_name,
+                v.rule.clone(),
+                v.detail.clone(),
+                v.severity.clone(),
+                v.session_id.clone().unwrap_or_default(),
+            ],
+        );
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"violations.csv\""),
+        ],
+        out,
+    )
+}
@@ -2,30 +2,36 @@
 //  screen_ws.rs — Live screen relay WebSocket endpoints
 //
 //  /ws/screen        — Student agents connect here, send JPEG frames
+//                      (upgrade signed by the agent, see agent_auth.rs)
 //  /ws/screen/view   — Teacher dashboard connects here, receives frames
+//                      (teacher session cookie or ?token=); add
+//                      ?classroom=<id> to only receive that room
 // ─────────────────────────────────────────────────────────────────
 
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::response::IntoResponse;
+use axum::Extension;
 use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
 use std::sync::Arc;
 
-use crate::state::AppState;
+use crate::agent_auth::AgentIdentity;
+use crate::auth::Identity;
+use crate::state::{AppState, ScreenViewer};
 
 // ── Student agent endpoint: /ws/screen ──────────────────────────
 
 pub async fn ws_screen_student(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
+    Extension(agent): Extension<AgentIdentity>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_student_screen(socket, state))
+    ws.on_upgrade(move |socket| handle_student_screen(socket, state, agent.hostname))
 }
 
-async fn handle_student_screen(socket: WebSocket, state: Arc<AppState>) {
-    let (_ws_tx, mut ws_rx) = socket.split();
-    let mut hostname = String::new();
-
+async fn handle_student_screen(socket: WebSocket, state: Arc<AppState>, hostname: String) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
     // Step 1: Wait for JSON handshake {"role":"student","hostname":"..."}
     if let Some(Ok(msg)) = ws_rx.next().await {
         let text = match msg {
@@ -50,28 +56,46 @@ async fn handle_student_screen(socket: WebSocket, state: Arc<AppState>) {
             return;
         }
 
-        hostname = parsed["hostname"]
-            .as_str()
-            .unwrap_or("unknown")
-            .to_string();
+        // The hostname is bound by the signed upgrade; the handshake must agree
+        let claimed = parsed["hostname"].as_str().unwrap_or("");
+        if claimed != hostname {
+            tracing::warn!("Screen WS: {hostname} claimed to be '{claimed}'");
+            return;
+        }
     } else {
         tracing::warn!("Screen WS: student disconnected before handshake");
         return;
     }
 
+    // Only used to close the stream from our side, e.g. when the agent is revoked
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Message>();
+    let mut send_task = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let closing = matches!(msg, Message::Close(_));
+            if ws_tx.send(msg).await.is_err() || closing {
+                break;
+            }
+        }
+    });
+    state.screen_streams.insert(hostname.clone(), tx.clone());
+
     tracing::info!("🖥️  Screen stream connected: {hostname}");
 
     // Notify all teacher dashboards that a new student appeared
     {
         let event = build_tagged_event("student_connected", &hostname);
-        broadcast_to_screen_teachers(&state, &event).await;
+        broadcast_to_screen_teachers(&state, &hostname, &event).await;
     }
 
     // Step 2: Receive binary JPEG frames and relay to teachers
-    while let Some(Ok(msg)) = ws_rx.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = ws_rx.next() => msg,
+            _ = &mut send_task => break,
+        };
+        let Some(Ok(msg)) = msg else { break };
         match msg {
             Message::Binary(data) => {
-                let data: Vec<u8> = data.into();
                 // Cache the latest frame
                 state.screen_latest.insert(hostname.clone(), data.clone());
 
@@ -79,7 +103,7 @@ async fn handle_student_screen(socket: WebSocket, state: Arc<AppState>) {
                 let tagged = build_tagged_frame(&hostname, &data);
 
                 // Relay to all connected teacher dashboards
-                broadcast_to_screen_teachers(&state, &tagged).await;
+                broadcast_to_screen_teachers(&state, &hostname, &tagged).await;
             }
             Message::Close(_) => break,
             _ => {}
@@ -87,23 +111,37 @@ async fn handle_student_screen(socket: WebSocket, state: Arc<AppState>) {
     }
 
     // Cleanup
+    state.screen_streams.remove_if(&hostname, |_, t| t.same_channel(&tx));
+    send_task.abort();
     tracing::info!("🔌 Screen stream disconnected: {hostname}");
     state.screen_latest.remove(&hostname);
 
     let event = build_tagged_event("student_disconnected", &hostname);
-    broadcast_to_screen_teachers(&state, &event).await;
+    broadcast_to_screen_teachers(&state, &hostname, &event).await;
 }
 
 // ── Teacher dashboard endpoint: /ws/screen/view ─────────────────
 
+#[derive(Deserialize)]
+pub struct ScreenViewQuery {
+    pub classroom: Option<String>,
+}
+
 pub async fn ws_screen_teacher(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Query(q): Query<ScreenViewQuery>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_teacher_screen(socket, state))
+    ws.on_upgrade(move |socket| handle_teacher_screen(socket, state, identity, q.classroom))
 }
 
-async fn handle_teacher_screen(socket: WebSocket, state: Arc<AppState>) {
+async fn handle_teacher_screen(
+    socket: WebSocket,
+    state: Arc<AppState>,
+    identity: Identity,
+    classroom: Option<String>,
+) {
     let (mut ws_tx, mut ws_rx) = socket.split();
 
     // Create a channel for sending frames to this teacher
@@ -112,10 +150,10 @@ async fn handle_teacher_screen(socket: WebSocket, state: Arc<AppState>) {
     // Register this teacher
     {
         let mut teachers = state.screen_teachers.write().await;
-        teachers.push(tx.clone());
+        teachers.push(ScreenViewer { tx: tx.clone(), classroom: classroom.clone() });
     }
-    tracing::info!("👁️  Screen viewer connected (teachers: {})",
-        state.screen_teachers.read().await.len());
+    tracing::info!("👁️  Screen viewer connected: {} (teachers: {})",
+        identity.username, state.screen_teachers.read().await.len());
 
     // Send the current student list as a JSON text message
     {
@@ -123,6 +161,7 @@ async fn handle_teacher_screen(socket: WebSocket, state: Arc<AppState>) {
             .screen_latest
             .iter()
             .map(|e| e.key().clone())
+            .filter(|h| state.in_classroom(h, classroom.as_deref()))
             .collect();
 
         let list_msg = serde_json::json!({
@@ -130,15 +169,18 @@ async fn handle_teacher_screen(socket: WebSocket, state: Arc<AppState>) {
             "students": student_hostnames,
         });
 
-        if let Err(e) = ws_tx.send(Message::Text(list_msg.to_string().into())).await {
+        if let Err(e) = ws_tx.send(Message::Text(list_msg.to_string())).await {
             tracing::warn!("Failed to send student list to teacher: {e}");
         }
     }
 
     // Send the latest cached frame for each student
     for entry in state.screen_latest.iter() {
+        if !state.in_classroom(entry.key(), classroom.as_deref()) {
+            continue;
+        }
         let tagged = build_tagged_frame(entry.key(), entry.value());
-        if ws_tx.send(Message::Binary(tagged.into())).await.is_err() {
+        if ws_tx.send(Message::Binary(tagged)).await.is_err() {
             break;
         }
     }
@@ -146,7 +188,7 @@ async fn handle_teacher_screen(socket: WebSocket, state: Arc<AppState>) {
     // Spawn a task that forwards channel messages to the WebSocket
     let send_task = tokio::spawn(async move {
         while let Some(data) = rx.recv().await {
-            if ws_tx.send(Message::Binary(data.into())).await.is_err() {
+            if ws_tx.send(Message::Binary(data)).await.is_err() {
                 break;
             }
         }
@@ -154,19 +196,19 @@ async fn handle_teacher_screen(socket: WebSocket, state: Arc<AppState>) {
 
     // Keep alive — read from the teacher socket to detect close
     while let Some(Ok(msg)) = ws_rx.next().await {
-        match msg {
-            Message::Close(_) => break,
-            _ => {} // Ignore any incoming messages
+        if let Message::Close(_) = msg {
+            break;
         }
+        // Ignore any other incoming messages
     }
 
     // Cleanup — remove this teacher's sender
     {
         let mut teachers = state.screen_teachers.write().await;
-        teachers.retain(|t| !t.is_closed());
+        teachers.retain(|t| !t.tx.is_closed());
     }
-    tracing::info!("👁️  Screen viewer disconnected (teachers: {})",
-        state.screen_teachers.read().await.len());
+    tracing::info!("👁️  Screen viewer disconnected: {} (teachers: {})",
+        identity.username, state.screen_teachers.read().await.len());
 
     send_task.abort();
 }
@@ -200,10 +242,12 @@ fn build_tagged_event(event_type: &str, hostname: &str) -> Vec<u8> {
     frame
 }
 
-/// Send data to all connected teacher screen viewers.
-async fn broadcast_to_screen_teachers(state: &AppState, data: &[u8]) {
+/// Send data about `hostname` to every teacher screen viewer watching its classroom.
+async fn broadcast_to_screen_teachers(state: &AppState, hostname: &str, data: &[u8]) {
     let teachers = state.screen_teachers.read().await;
-    for tx in teachers.iter() {
-        let _ = tx.send(data.to_vec());
+    for viewer in teachers.iter() {
+        if state.in_classroom(hostname, viewer.classroom.as_deref()) {
+            let _ = viewer.tx.send(data.to_vec());
+        }
     }
 }
@@ -0,0 +1,297 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::audit;
+use crate::auth::{self, Identity};
+use crate::lessons;
+use crate::models::LessonSession;
+use crate::redis_store;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct StartLessonRequest {
+    pub name: String,
+    pub classroom: Option<String>,
+}
+
+/// Either the lesson id, or the scope whose running lesson should end
+#[derive(Deserialize)]
+pub struct EndLessonRequest {
+    pub id: Option<String>,
+    pub classroom: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LessonListQuery {
+    pub classroom: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+    pub hostname: Option<String>,
+}
+
+async fn load_lesson(state: &AppState, id: &str) -> Result<LessonSession, StatusCode> {
+    let mut conn = state.redis.clone();
+    redis_store::get_lesson(&mut conn, &state.config.key_prefix, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// POST /api/sessions/start
+/// Body: { "name": "3rd period Tuesday", "classroom": "lab2" }
+/// Without a classroom the lesson covers every student.
+pub async fn start_lesson(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<StartLessonRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    if let Some(room) = &body.classroom {
+        redis_store::get_classroom(&mut conn, prefix, room)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+    }
+
+    let now = Utc::now();
+    let lesson = LessonSession {
+        id: format!("{}-{}", now.format("%Y%m%d-%H%M%S"), &auth::new_token()[..6]),
+        name: if body.name.trim().is_empty() {
+            now.format("Lesson %Y-%m-%d %H:%M").to_string()
+        } else {
+            body.name.trim().to_string()
+        },
+        classroom: body.classroom,
+        started_by: identity.username.clone(),
+        started_at: now,
+        ended_at: None,
+    };
+
+    let scope = lessons::scope_of(&lesson).to_string();
+    if let Some(running) = state.active_lessons.get(&scope) {
+        return Ok(Json(json!({
+            "status": "error",
+            "error": format!("Lesson '{}' is already running.", running.name),
+            "session": running.clone(),
+        })));
+    }
+
+    redis_store::store_lesson(&mut conn, prefix, &lesson)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    redis_store::set_active_lesson(&mut conn, prefix, &scope, Some(&lesson.id))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    state.active_lessons.insert(scope.clone(), lesson.clone());
+
+    let params = json!({ "id": lesson.id, "name": lesson.name, "scope": scope });
+    audit::record(&state, &identity, "start_lesson", vec![], params, vec![]).await;
+    tracing::info!("📚 {} started lesson '{}' ({scope})", identity.username, lesson.name);
+    Ok(Json(json!(lesson)))
+}
+
+/// POST /api/sessions/end
+/// Body: { "id": "..." } or { "classroom": "lab2" } — {} ends the global lesson
+pub async fn end_lesson(
+    State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<EndLessonRequest>,
+) -> Result<Json<LessonSession>, StatusCode> {
+    let mut lesson = match &body.id {
+        Some(id) => load_lesson(&state, id).await?,
+        None => {
+            let scope = body.classroom.as_deref().unwrap_or(lessons::ALL_SCOPE);
+            state
+                .active_lessons
+                .get(scope)
+                .map(|l| l.clone())
+                .ok_or(StatusCode::NOT_FOUND)?
+        }
+    };
+    if lesson.ended_at.is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    lesson.ended_at = Some(Utc::now());
+    redis_store::store_lesson(&mut conn, prefix, &lesson)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let scope = lessons::scope_of(&lesson).to_string();
+    redis_store::set_active_lesson(&mut conn, prefix, &scope, None)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    state.active_lessons.remove(&scope);
+
+    let params = json!({ "id": lesson.id, "name": lesson.name, "scope": scope });
+    audit::record(&state, &identity, "end_lesson", vec![], params, vec![]).await;
+    tracing::info!("📚 {} ended lesson '{}'", identity.username, lesson.name);
+    Ok(Json(lesson))
+}
+
+/// GET /api/sessions?classroom=<id> — all lessons, newest first
+pub async fn list_lessons(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<LessonListQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut conn = state.redis.clone();
+    let mut all = redis_store::get_all_lessons(&mut conn, &state.config.key_prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    all.retain(|l| q.classroom.is_none() || l.classroom == q.classroom);
+    all.sort_by_key(|l| std::cmp::Reverse(l.started_at));
+
+    Ok(Json(json!({
+        "count": all.len(),
+        "sessions": all,
+    })))
+}
+
+/// GET /api/sessions/active — lessons running right now
+pub async fn active_lessons(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let mut running: Vec<LessonSession> =
+        state.active_lessons.iter().map(|e| e.value().clone()).collect();
+    running.sort_by_key(|l| l.started_at);
+
+    Json(json!({
+        "count": running.len(),
+        "sessions": running,
+    }))
+}
+
+/// GET /api/sessions/:id
+pub async fn get_lesson(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<LessonSession>, StatusCode> {
+    Ok(Json(load_lesson(&state, &id).await?))
+}
+
+/// GET /api/sessions/:id/violations — everything reported during the lesson, oldest first
+pub async fn lesson_violations(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let lesson = load_lesson(&state, &id).await?;
+    let mut conn = state.redis.clone();
+    let violations = redis_store::get_lesson_violations(&mut conn, &state.config.key_prefix, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(json!({
+        "session": lesson,
+        "total": violations.len(),
+        "violations": violations,
+    })))
+}
+
+/// GET /api/sessions/:id/attendance
+/// Students seen during the lesson, plus registered students of its
+/// classroom (or everyone, for a global lesson) that never showed up.
+pub async fn lesson_attendance(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let lesson = load_lesson(&state, &id).await?;
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let mut present = redis_store::get_all_attendance(&mut conn, prefix, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    present.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+
+    let agents = redis_store::get_all_agents(&mut conn, prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut absent: Vec<String> = agents
+        .iter()
+        .map(|e| e.split('|').next().unwrap_or(e).to_string())
+        .filter(|h| state.in_classroom(h, lesson.classroom.as_deref()))
+        .filter(|h| !present.iter().any(|p| &p.hostname == h))
+        .collect();
+    absent.sort();
+    absent.dedup();
+
+    Ok(Json(json!({
+        "session": lesson,
+        "present_count": present.len(),
+        "present": present,
+        "absent": absent,
+    })))
+}
+
+/// GET /api/sessions/:id/activity?hostname=<h>
+/// Per-student counts of what each agent sent during the lesson. With a
+/// hostname, also that student's notifications and recent app snapshots.
+pub async fn lesson_activity(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(q): Query<ActivityQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let lesson = load_lesson(&state, &id).await?;
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    if let Some(hostname) = &q.hostname {
+        let counts: HashMap<String, i64> =
+            redis_store::get_lesson_activity(&mut conn, prefix, &id, hostname)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .into_iter()
+                .collect();
+        let notifications: Vec<_> = redis_store::get_lesson_notifications(&mut conn, prefix, &id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|n| &n.hostname == hostname)
+            .collect();
+        let apps = redis_store::get_lesson_apps(&mut conn, prefix, &id, hostname, 20)
+            .await
+            .unwrap_or_default();
+
+        return Ok(Json(json!({
+            "session": lesson,
+            "hostname": hostname,
+            "counts": counts,
+            "notifications": notifications,
+            "apps": apps,
+        })));
+    }
+
+    let mut hostnames: Vec<String> = redis_store::get_all_attendance(&mut conn, prefix, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(|r| r.hostname)
+        .collect();
+    hostnames.sort();
+
+    let mut students = Vec::with_capacity(hostnames.len());
+    for hostname in hostnames {
+        let counts: HashMap<String, i64> =
+            redis_store::get_lesson_activity(&mut conn, prefix, &id, &hostname)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .collect();
+        students.push(json!({ "hostname": hostname, "counts": counts }));
+    }
+
+    Ok(Json(json!({
+        "session": lesson,
+        "students": students,
+    })))
+}
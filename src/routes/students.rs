@@ -1,20 +1,30 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
-use axum::Json;
+use axum::{Extension, Json};
+use serde::Deserialize;
 use serde_json::Value;
 use std::sync::Arc;
 
-use crate::models::{StudentDetail, StudentSummary};
+use crate::auth::Identity;
+use crate::layout;
+use crate::models::{Role, StudentDetail, StudentSummary};
+use crate::overrides;
 use crate::redis_store;
 use crate::state::AppState;
 
+#[derive(Deserialize)]
+pub struct StudentQuery {
+    pub classroom: Option<String>,
+}
+
 /// Build a StudentSummary from an agent registry entry like "hostname|ip|port"
 async fn build_summary(
+    state: &AppState,
     conn: &mut redis::aio::ConnectionManager,
-    prefix: &str,
     entry: &str,
-    heartbeat_ttl: u64,
 ) -> Option<StudentSummary> {
+    let prefix = &state.config.key_prefix;
+    let heartbeat_ttl = state.config.heartbeat_ttl_secs;
     let parts: Vec<&str> = entry.split('|').collect();
     if parts.len() < 3 {
         return None;
@@ -27,6 +37,9 @@ async fn build_summary(
     let violation_count = redis_store::get_violation_count(conn, prefix, hostname)
         .await
         .unwrap_or(0);
+    let lock = redis_store::get_lock_state(conn, prefix, hostname)
+        .await
+        .unwrap_or_default();
 
     let (active, os, username, cpu_usage, ram_usage, last_seen) = match hb {
         Some(h) => {
@@ -51,34 +64,62 @@ async fn build_summary(
         port,
         active,
         os,
-        username,
         cpu_usage,
         ram_usage,
         violation_count,
         last_seen,
+        classroom: state.student_rooms.get(hostname).map(|r| r.clone()),
+        student: state.student_for(hostname, &username),
+        username,
+        seat: None,
+        lock,
     })
 }
 
-/// GET /api/students — all registered students
-pub async fn list_students(
-    State(state): State<Arc<AppState>>,
-) -> Result<Json<Value>, StatusCode> {
+/// Summaries of registered students, optionally limited to one classroom
+/// and/or to those with a live heartbeat, in seating-chart order.
+pub(crate) async fn collect_students(
+    state: &AppState,
+    classroom: Option<&str>,
+    active_only: bool,
+) -> Result<Vec<StudentSummary>, StatusCode> {
     let mut conn = state.redis.clone();
-    let prefix = &state.config.key_prefix;
-
-    let agents = redis_store::get_all_agents(&mut conn, prefix)
+    let agents = redis_store::get_all_agents(&mut conn, &state.config.key_prefix)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let mut students = Vec::new();
     for entry in &agents {
-        if let Some(s) =
-            build_summary(&mut conn, prefix, entry, state.config.heartbeat_ttl_secs).await
-        {
-            students.push(s);
+        let hostname = entry.split('|').next().unwrap_or(entry);
+        if !state.in_classroom(hostname, classroom) {
+            continue;
+        }
+        if let Some(s) = build_summary(state, &mut conn, entry).await {
+            if s.active || !active_only {
+                students.push(s);
+            }
         }
     }
-    students.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+
+    let seats = layout::for_room(state, classroom)
+        .await
+        .map(|l| l.seat_map())
+        .unwrap_or_default();
+    for s in &mut students {
+        s.seat = seats.get(&s.hostname).copied();
+    }
+    students.sort_by(|a, b| {
+        layout::seat_order(&seats, &a.hostname).cmp(&layout::seat_order(&seats, &b.hostname))
+    });
+    Ok(students)
+}
+
+/// GET /api/students?classroom=<id> — all registered students
+pub async fn list_students(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<StudentQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let students = collect_students(&state, q.classroom.as_deref(), false).await?;
 
     Ok(Json(serde_json::json!({
         "count": students.len(),
@@ -86,28 +127,12 @@ pub async fn list_students(
     })))
 }
 
-/// GET /api/students/active — only students with live heartbeat
+/// GET /api/students/active?classroom=<id> — only students with live heartbeat
 pub async fn list_active(
     State(state): State<Arc<AppState>>,
+    Query(q): Query<StudentQuery>,
 ) -> Result<Json<Value>, StatusCode> {
-    let mut conn = state.redis.clone();
-    let prefix = &state.config.key_prefix;
-
-    let agents = redis_store::get_all_agents(&mut conn, prefix)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let mut active = Vec::new();
-    for entry in &agents {
-        if let Some(s) =
-            build_summary(&mut conn, prefix, entry, state.config.heartbeat_ttl_secs).await
-        {
-            if s.active {
-                active.push(s);
-            }
-        }
-    }
-    active.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+    let active = collect_students(&state, q.classroom.as_deref(), true).await?;
 
     Ok(Json(serde_json::json!({
         "count": active.len(),
@@ -116,8 +141,11 @@ pub async fn list_active(
 }
 
 /// GET /api/students/:hostname — full details for one student
+/// Observers get the summary, screenshot and apps; violations and
+/// notifications are for teachers, as with GET /api/violations.
 pub async fn student_detail(
     State(state): State<Arc<AppState>>,
+    Extension(identity): Extension<Identity>,
     Path(hostname): Path<String>,
 ) -> Result<Json<StudentDetail>, StatusCode> {
     let mut conn = state.redis.clone();
@@ -133,10 +161,9 @@ pub async fn student_detail(
         .find(|e| e.starts_with(&format!("{hostname}|")))
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    let summary =
-        build_summary(&mut conn, prefix, entry, state.config.heartbeat_ttl_secs)
-            .await
-            .ok_or(StatusCode::NOT_FOUND)?;
+    let summary = build_summary(&state, &mut conn, entry)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
 
     let screenshot = redis_store::get_screenshot(&mut conn, prefix, &hostname)
         .await
@@ -144,13 +171,18 @@ pub async fn student_detail(
     let apps = redis_store::get_apps(&mut conn, prefix, &hostname)
         .await
         .unwrap_or(None);
-    let notifications =
-        redis_store::get_notifications(&mut conn, prefix, &hostname, 50)
+    let (notifications, violations) = if identity.role >= Role::Teacher {
+        let notifications = redis_store::get_notifications(&mut conn, prefix, &hostname, 50)
             .await
             .unwrap_or_default();
-    let violations = redis_store::get_violations(&mut conn, prefix, &hostname, 50)
-        .await
-        .unwrap_or_default();
+        let violations = redis_store::get_violations(&mut conn, prefix, &hostname, 50)
+            .await
+            .unwrap_or_default();
+        (notifications, violations)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let overrides = overrides::active_for(&state, &mut conn, &hostname, &summary.username).await;
 
     Ok(Json(StudentDetail {
         summary,
@@ -158,5 +190,6 @@ pub async fn student_detail(
         apps,
         notifications,
         violations,
+        overrides,
     }))
 }
@@ -0,0 +1,42 @@
+use axum::extract::State;
+use axum::Json;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::state::AppState;
+
+/// GET /api/agents/throttle — agents that hit the rate limit, and client
+/// addresses that sent bodies over the caps
+pub async fn throttle_stats(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let mut stats = state.agent_limiter.snapshot();
+    stats.sort_by_key(|(_, s)| std::cmp::Reverse(s.rate_limited));
+
+    let agents: Vec<Value> = stats
+        .into_iter()
+        .map(|(hostname, s)| json!({
+            "hostname": hostname,
+            "rate_limited": s.rate_limited,
+            "last_throttled": s.last_throttled,
+        }))
+        .collect();
+
+    let mut peers = state.agent_limiter.oversized_snapshot();
+    peers.sort_by_key(|(_, s)| std::cmp::Reverse(s.count));
+    let oversized: Vec<Value> = peers
+        .into_iter()
+        .map(|(address, s)| json!({
+            "address": address.to_string(),
+            "oversized": s.count,
+            "claimed_hostname": s.claimed_hostname,
+            "last_at": s.last_at,
+        }))
+        .collect();
+
+    Json(json!({
+        "rate_per_sec": state.config.agent_rate_per_sec,
+        "burst": state.config.agent_rate_burst,
+        "count": agents.len(),
+        "agents": agents,
+        "oversized": oversized,
+    }))
+}
@@ -5,6 +5,7 @@ use serde::Deserialize;
 use serde_json::Value;
 use std::sync::Arc;
 
+use crate::models::Violation;
 use crate::redis_store;
 use crate::state::AppState;
 
@@ -12,6 +13,35 @@ use crate::state::AppState;
 pub struct ViolationQuery {
     pub hostname: Option<String>,
     pub count: Option<isize>,
+    pub classroom: Option<String>,
+}
+
+/// Most recent violations across all known agents (optionally one classroom), newest first.
+pub(crate) async fn recent_violations(
+    state: &AppState,
+    classroom: Option<&str>,
+    count: isize,
+) -> Vec<Violation> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+    let agents = redis_store::get_all_agents(&mut conn, prefix)
+        .await
+        .unwrap_or_default();
+
+    let mut all = Vec::new();
+    for entry in &agents {
+        let hostname = entry.split('|').next().unwrap_or(entry);
+        if !state.in_classroom(hostname, classroom) {
+            continue;
+        }
+        let viols = redis_store::get_violations(&mut conn, prefix, hostname, count)
+            .await
+            .unwrap_or_default();
+        all.extend(viols);
+    }
+    all.sort_by_key(|v| std::cmp::Reverse(v.timestamp));
+    all.truncate(count.max(0) as usize);
+    all
 }
 
 pub async fn violations(
@@ -35,24 +65,8 @@ pub async fn violations(
             "violations": viols
         })))
     } else {
-        // Return violations for all known agents
-        let agents = redis_store::get_all_agents(&mut conn, prefix)
-            .await
-            .unwrap_or_default();
-
-        let mut all = Vec::new();
-        for entry in &agents {
-            let hostname = entry.split('|').next().unwrap_or(entry);
-            let viols = redis_store::get_violations(&mut conn, prefix, hostname, count)
-                .await
-                .unwrap_or_default();
-            for v in viols {
-                all.push(v);
-            }
-        }
-        all.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-        all.truncate(count as usize);
-
+        // Return violations for all known agents (or one classroom)
+        let all = recent_violations(&state, q.classroom.as_deref(), count).await;
         Ok(Json(serde_json::json!({ "violations": all })))
     }
 }
@@ -1,35 +1,100 @@
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::State;
 use axum::response::IntoResponse;
+use axum::Extension;
 use chrono::Utc;
 use futures::{SinkExt, StreamExt};
 use std::sync::Arc;
 
-use crate::state::AppState;
+use crate::agent_auth::AgentIdentity;
+use crate::auth::{self, Identity};
+use crate::models::Role;
+use crate::routes::presence;
+use crate::state::{AppState, TeacherSession, WsTx};
 
+/// GET /ws — chat / event socket. The client id is bound from the
+/// authenticated upgrade: teachers join as "teacher" (one session per
+/// dashboard, all of which receive events), agents as their hostname.
+/// Observers get presence only: no student events and no chat.
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
+    teacher: Option<Extension<Identity>>,
+    agent: Option<Extension<AgentIdentity>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    let (client_id, teacher) = match (teacher, agent) {
+        (Some(Extension(identity)), _) => {
+            tracing::info!("WS teacher connection from {}", identity.username);
+            ("teacher".to_string(), Some(identity))
+        }
+        (None, Some(Extension(agent))) => (agent.hostname, None),
+        // authorize() guarantees one of the two
+        (None, None) => return axum::http::StatusCode::UNAUTHORIZED.into_response(),
+    };
+    ws.on_upgrade(move |socket| handle_socket(socket, state, client_id, teacher))
 }
 
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
+async fn handle_socket(
+    socket: WebSocket,
+    state: Arc<AppState>,
+    client_id: String,
+    teacher: Option<Identity>,
+) {
     let (mut ws_tx, mut ws_rx) = socket.split();
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Message>();
 
-    // Forward channel → WebSocket
-    let send_task = tokio::spawn(async move {
+    // Forward channel → WebSocket, until a close is sent from our side
+    let mut send_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
-            if ws_tx.send(msg).await.is_err() {
+            let closing = matches!(msg, Message::Close(_));
+            if ws_tx.send(msg).await.is_err() || closing {
                 break;
             }
         }
     });
 
-    let mut client_id = String::new();
+    // Name shown to others: the teacher's account, or the agent's hostname
+    let name = teacher.as_ref().map_or_else(|| client_id.clone(), |t| t.username.clone());
+    // Observers may watch but not take part in chat
+    let can_chat = teacher.as_ref().is_none_or(|t| t.role >= Role::Teacher);
+    let session_id = match &teacher {
+        Some(identity) => {
+            let id = auth::new_token()[..12].to_string();
+            state.teachers.insert(
+                id.clone(),
+                TeacherSession {
+                    username: identity.username.clone(),
+                    role: identity.role,
+                    tx: tx.clone(),
+                    connected_at: Utc::now(),
+                    viewing: None,
+                },
+            );
+            presence::announce(&state);
+            Some(id)
+        }
+        None => {
+            state.ws_clients.insert(client_id.clone(), tx.clone());
+            None
+        }
+    };
+    let sys_msg = serde_json::json!({
+        "type": "system",
+        "content": format!("{name} joined the chat"),
+        "timestamp": Utc::now().to_rfc3339()
+    });
+    if can_chat {
+        broadcast(&state, &sys_msg.to_string(), Some(&tx));
+    }
+    tracing::info!("WS client connected: {name}");
 
-    while let Some(Ok(msg)) = ws_rx.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = ws_rx.next() => msg,
+            // Closed from our side, e.g. the agent was revoked
+            _ = &mut send_task => break,
+        };
+        let Some(Ok(msg)) = msg else { break };
         let text = match msg {
             Message::Text(ref t) => t.to_string(),
             Message::Close(_) => break,
@@ -42,29 +107,34 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
         };
 
         match parsed["type"].as_str() {
+            // Identity comes from the handshake; self-declared ids are ignored
             Some("identify") => {
-                client_id = parsed["id"]
-                    .as_str()
-                    .unwrap_or("unknown")
-                    .to_string();
-                state.ws_clients.insert(client_id.clone(), tx.clone());
-
-                let sys_msg = serde_json::json!({
+                let ack = serde_json::json!({
                     "type": "system",
-                    "content": format!("{} joined the chat", client_id),
+                    "content": format!("identified as {client_id}"),
                     "timestamp": Utc::now().to_rfc3339()
                 });
-                broadcast(&state, &sys_msg.to_string(), &client_id);
-                tracing::info!("WS client identified: {client_id}");
+                let _ = tx.send(Message::Text(ack.to_string()));
             }
 
-            Some("chat") => {
+            // Dashboard opened (or closed, with no hostname) a student's detail view
+            Some("viewing") => {
+                let Some(id) = &session_id else { continue };
+                let viewing = parsed["hostname"].as_str().filter(|h| !h.is_empty()).map(str::to_string);
+                if let Some(mut session) = state.teachers.get_mut(id) {
+                    session.viewing = viewing;
+                }
+                presence::announce(&state);
+            }
+
+            Some("chat") if can_chat => {
                 let to = parsed["to"].as_str().unwrap_or("all");
                 let content = parsed["content"].as_str().unwrap_or("");
 
                 let out = serde_json::json!({
                     "type": "chat",
                     "from": client_id,
+                    "teacher": teacher.as_ref().map(|t| &t.username),
                     "to": to,
                     "content": content,
                     "timestamp": Utc::now().to_rfc3339()
@@ -72,14 +142,20 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                 let out_str = out.to_string();
 
                 if to == "all" {
-                    broadcast(&state, &out_str, "");
+                    broadcast(&state, &out_str, None);
                 } else {
-                    // Send to target
+                    // "teacher" reaches every dashboard, and teachers all
+                    // follow the conversations any of them take part in
+                    if to == "teacher" || teacher.is_some() {
+                        state.notify_teachers(&out);
+                    }
                     if let Some(target_tx) = state.ws_clients.get(to) {
-                        let _ = target_tx.send(Message::Text(out_str.clone().into()));
+                        let _ = target_tx.send(Message::Text(out_str.clone()));
+                    }
+                    // Echo back to a student sender
+                    if teacher.is_none() {
+                        let _ = tx.send(Message::Text(out_str));
                     }
-                    // Echo back to sender
-                    let _ = tx.send(Message::Text(out_str.into()));
                 }
             }
 
@@ -87,25 +163,40 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
         }
     }
 
-    // Cleanup
-    if !client_id.is_empty() {
-        state.ws_clients.remove(&client_id);
+    // Cleanup — agents only if a newer connection hasn't taken over the id
+    let left = match &session_id {
+        Some(id) => {
+            state.teachers.remove(id);
+            presence::announce(&state);
+            true
+        }
+        None => state.ws_clients.remove_if(&client_id, |_, t| t.same_channel(&tx)).is_some(),
+    };
+    if left && can_chat {
         let sys_msg = serde_json::json!({
             "type": "system",
-            "content": format!("{} left the chat", client_id),
+            "content": format!("{name} left the chat"),
             "timestamp": Utc::now().to_rfc3339()
         });
-        broadcast(&state, &sys_msg.to_string(), &client_id);
-        tracing::info!("WS client disconnected: {client_id}");
+        broadcast(&state, &sys_msg.to_string(), Some(&tx));
     }
+    tracing::info!("WS client disconnected: {name}");
 
     send_task.abort();
 }
 
-fn broadcast(state: &AppState, msg: &str, exclude: &str) {
+/// Send to every agent and teacher, except the connection behind `exclude`.
+/// Observers are left out.
+fn broadcast(state: &AppState, msg: &str, exclude: Option<&WsTx>) {
+    let skip = |tx: &WsTx| exclude.is_some_and(|e| e.same_channel(tx));
     for entry in state.ws_clients.iter() {
-        if entry.key() != exclude {
-            let _ = entry.value().send(Message::Text(msg.to_string().into()));
+        if !skip(entry.value()) {
+            let _ = entry.value().send(Message::Text(msg.to_string()));
+        }
+    }
+    for t in state.teachers.iter().filter(|t| t.role >= Role::Teacher) {
+        if !skip(&t.tx) {
+            let _ = t.tx.send(Message::Text(msg.to_string()));
         }
     }
 }
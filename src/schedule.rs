@@ -0,0 +1,164 @@
+// ─────────────────────────────────────────────────────────────────
+//  schedule.rs — Timetable-driven ban profiles
+//
+//  Every 30 seconds the weekly timetable in config.toml is checked
+//  against the local clock. When the matching slot changes, the
+//  global policy switches to that slot's profile (or back to the
+//  config.toml lists) and {prefix}:ban_config is republished.
+//  A slot that ends before it starts (22:00-06:00) runs past
+//  midnight into the next day; its days are the days it starts on.
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Weekday};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{Config, ScheduleSlot};
+use crate::policy;
+use crate::state::AppState;
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+impl ScheduleSlot {
+    pub fn matches(&self, at: NaiveDateTime) -> bool {
+        let (Some(start), Some(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return false;
+        };
+        let on = |day: Weekday| self.days.iter().filter_map(|d| d.parse::<Weekday>().ok()).any(|d| d == day);
+        let time = at.time();
+        if start < end {
+            on(at.weekday()) && start <= time && time < end
+        } else if start > end {
+            (on(at.weekday()) && start <= time) || (on(at.weekday().pred()) && time < end)
+        } else {
+            false
+        }
+    }
+}
+
+/// Profile the timetable calls for at `at` (first matching slot wins)
+pub fn profile_at(cfg: &Config, at: NaiveDateTime) -> Option<String> {
+    cfg.schedule
+        .iter()
+        .find(|slot| slot.matches(at))
+        .map(|slot| slot.profile.clone())
+}
+
+/// Warn about timetable entries that can never apply.
+fn validate(cfg: &Config) {
+    for (i, slot) in cfg.schedule.iter().enumerate() {
+        match (parse_time(&slot.start), parse_time(&slot.end)) {
+            (Some(start), Some(end)) if start != end => {}
+            _ => tracing::warn!(
+                "Schedule slot {i}: invalid times '{}'-'{}' (use HH:MM, start and end apart)",
+                slot.start, slot.end
+            ),
+        }
+        for day in &slot.days {
+            if day.parse::<Weekday>().is_err() {
+                tracing::warn!("Schedule slot {i}: unknown day '{day}'");
+            }
+        }
+        if !cfg.policy_profiles.contains_key(&slot.profile) {
+            tracing::warn!("Schedule slot {i}: unknown profile '{}'", slot.profile);
+        }
+    }
+}
+
+/// Background task: switch profiles at timetable boundaries.
+pub async fn schedule_task(state: Arc<AppState>) {
+    if state.config.schedule.is_empty() {
+        return;
+    }
+    validate(&state.config);
+
+    // Profile last pushed to Redis; retried on the next tick if publishing fails
+    let mut published: Option<Option<String>> = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(30));
+    loop {
+        interval.tick().await;
+
+        let wanted = profile_at(&state.config, Local::now().naive_local())
+            .filter(|name| state.config.policy_profiles.contains_key(name));
+        if published.as_ref() == Some(&wanted) {
+            continue;
+        }
+        state.policy.write().unwrap().active_profile = wanted.clone();
+
+        let effective = state.effective_policy();
+        let mut conn = state.redis.clone();
+        if let Err(e) = policy::publish(&mut conn, &state.config.key_prefix, None, &effective).await {
+            tracing::warn!("Could not publish scheduled ban config: {e}");
+            continue;
+        }
+        tracing::info!(
+            "🕒 Ban profile switched to {} — {} apps, {} sites, SAU={}",
+            wanted.as_deref().unwrap_or("default"),
+            effective.banned_apps.len(),
+            effective.banned_sites.len(),
+            effective.sau_mode
+        );
+
+        state.notify_teachers(&serde_json::json!({
+            "type": "policy_profile",
+            "profile": wanted,
+        }));
+        published = Some(wanted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn slot(days: &[&str], start: &str, end: &str) -> ScheduleSlot {
+        ScheduleSlot {
+            days: days.iter().map(|d| d.to_string()).collect(),
+            start: start.to_string(),
+            end: end.to_string(),
+            profile: "exam".to_string(),
+        }
+    }
+
+    /// 2024-01-01 was a Monday
+    fn at(day: u32, time: &str) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_time(parse_time(time).unwrap())
+    }
+
+    #[test]
+    fn day_names_short_and_long() {
+        let s = slot(&["mon", "Wednesday"], "08:00", "09:00");
+        assert!(s.matches(at(1, "08:30")));
+        assert!(!s.matches(at(2, "08:30")));
+        assert!(s.matches(at(3, "08:30")));
+        assert!(!slot(&["someday"], "08:00", "09:00").matches(at(1, "08:30")));
+    }
+
+    #[test]
+    fn start_is_inclusive_and_end_exclusive() {
+        let s = slot(&["Mon"], "08:00", "09:00");
+        assert!(!s.matches(at(1, "07:59")));
+        assert!(s.matches(at(1, "08:00")));
+        assert!(s.matches(at(1, "08:59")));
+        assert!(!s.matches(at(1, "09:00")));
+    }
+
+    #[test]
+    fn slots_crossing_midnight_run_into_the_next_day() {
+        let s = slot(&["Sun"], "22:00", "06:00");
+        assert!(s.matches(at(7, "22:00")));
+        assert!(s.matches(at(8, "05:59")));
+        assert!(!s.matches(at(8, "06:00")));
+        assert!(!s.matches(at(8, "22:00")));
+        assert!(!s.matches(at(7, "05:00")));
+    }
+
+    #[test]
+    fn empty_or_unparsable_slots_never_match() {
+        assert!(!slot(&["Mon"], "08:00", "08:00").matches(at(1, "08:00")));
+        assert!(!slot(&["Mon"], "8am", "09:00").matches(at(1, "08:30")));
+    }
+}
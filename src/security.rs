@@ -0,0 +1,114 @@
+// ─────────────────────────────────────────────────────────────────
+//  security.rs — CORS policy and browser security headers
+// ─────────────────────────────────────────────────────────────────
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::Config;
+use crate::state::AppState;
+
+/// CORS from config. With no allowed origins no CORS headers are sent,
+/// so browsers only allow same-origin requests.
+pub fn cors_layer(cfg: &Config) -> CorsLayer {
+    if cfg.cors_allowed_origins.is_empty() {
+        return CorsLayer::new();
+    }
+
+    // A wildcard cannot be combined with credentials (tower-http panics
+    // on it), so with credentials only the listed origins are allowed
+    let wildcard = cfg.cors_allowed_origins.iter().any(|o| o == "*");
+    let origins = if wildcard && !cfg.cors_allow_credentials {
+        AllowOrigin::any()
+    } else {
+        if wildcard {
+            tracing::warn!("⚠️ cors_allowed_origins \"*\" ignored: not allowed with cors_allow_credentials");
+        }
+        let listed: Vec<HeaderValue> = cfg
+            .cors_allowed_origins
+            .iter()
+            .filter(|o| *o != "*")
+            .filter_map(|o| HeaderValue::from_str(o).ok())
+            .collect();
+        if listed.is_empty() {
+            return CorsLayer::new();
+        }
+        AllowOrigin::list(listed)
+    };
+    let methods: Vec<Method> = cfg
+        .cors_allowed_methods
+        .iter()
+        .filter_map(|m| m.parse().ok())
+        .collect();
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+        .allow_credentials(cfg.cors_allow_credentials)
+}
+
+/// Middleware: add CSP, anti-framing and related headers unless a handler set them.
+pub async fn security_headers(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let mut resp = next.run(req).await;
+    let headers = resp.headers_mut();
+
+    if let Ok(csp) = HeaderValue::from_str(&state.config.content_security_policy) {
+        headers.entry(header::CONTENT_SECURITY_POLICY).or_insert(csp);
+    }
+    let fixed: [(HeaderName, &'static str); 4] = [
+        (header::X_FRAME_OPTIONS, "DENY"),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        (header::REFERRER_POLICY, "no-referrer"),
+        (HeaderName::from_static("permissions-policy"), "camera=(), microphone=(), geolocation=()"),
+    ];
+    for (name, value) in fixed {
+        headers.entry(name).or_insert(HeaderValue::from_static(value));
+    }
+    if state.config.tls_enabled {
+        headers
+            .entry(header::STRICT_TRANSPORT_SECURITY)
+            .or_insert(HeaderValue::from_static("max-age=31536000"));
+    }
+
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::Layer;
+
+    fn config(origins: &[&str], credentials: bool) -> Config {
+        Config {
+            cors_allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+            cors_allow_credentials: credentials,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn every_origin_and_credentials_combination_builds() {
+        let origin_sets: [&[&str]; 5] = [
+            &[],
+            &["*"],
+            &["https://teacher.example"],
+            &["*", "https://teacher.example"],
+            &["not a header\n"],
+        ];
+        for origins in origin_sets {
+            for credentials in [false, true] {
+                // The rules are checked (and would panic) when the layer wraps a service
+                let _ = cors_layer(&config(origins, credentials)).layer(());
+            }
+        }
+    }
+}
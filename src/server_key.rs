@@ -0,0 +1,135 @@
+// ─────────────────────────────────────────────────────────────────
+//  server_key.rs — The backend's Ed25519 identity
+//
+//  Generated on first start and kept in `signing_key_path`; the public
+//  half is published to Redis so agents can verify teacher commands.
+//  Signed message (newline separated):
+//    METHOD, path, target hostname, nonce, expiry (unix secs), body
+// ─────────────────────────────────────────────────────────────────
+
+use ed25519_dalek::{Signer, SigningKey};
+use rand::rngs::OsRng;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const TARGET_HEADER: &str = "x-command-target";
+pub const NONCE_HEADER: &str = "x-command-nonce";
+pub const EXPIRES_HEADER: &str = "x-command-expires";
+pub const SIGNATURE_HEADER: &str = "x-command-signature";
+/// Queued command id, so agents can drop a command they already ran (not signed)
+pub const COMMAND_ID_HEADER: &str = "x-command-id";
+
+/// How long a signed command stays valid for the agent
+pub const COMMAND_TTL_SECS: i64 = 30;
+
+/// Load the signing key from `path`, generating and saving a new one if absent.
+/// Refuses a key file other local users can read: they could forge commands.
+pub fn load_or_generate(path: &str) -> Result<SigningKey, String> {
+    if let Ok(content) = fs::read_to_string(path) {
+        check_private(path)?;
+        let seed = hex::decode(content.trim())
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+        match seed {
+            Some(seed) => return Ok(SigningKey::from_bytes(&seed)),
+            None => tracing::warn!("Signing key at {path} is malformed, generating a new one"),
+        }
+    }
+
+    let key = SigningKey::generate(&mut OsRng);
+    match write_private(path, hex::encode(key.to_bytes()).as_bytes()) {
+        Ok(()) => tracing::info!("🔑 Generated new server signing key at {path}"),
+        Err(e) => tracing::warn!("Could not persist signing key to {path}: {e}"),
+    }
+    Ok(key)
+}
+
+/// Write a secret readable by this user only (0600 on Unix).
+pub fn write_private(path: &str, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // `mode` only applies to new files
+        if Path::new(path).exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(contents)
+}
+
+/// Error if a secret at `path` is readable by group or others (Unix only).
+#[cfg(unix)]
+fn check_private(path: &str) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path)
+        .map_err(|e| format!("Cannot stat {path}: {e}"))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        return Err(format!(
+            "{path} is accessible to other users (mode {:o}); run `chmod 600 {path}`",
+            mode & 0o777
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_private(_path: &str) -> Result<(), String> {
+    Ok(())
+}
+
+/// Hex-encoded public key, as published to Redis
+pub fn public_key_hex(key: &SigningKey) -> String {
+    hex::encode(key.verifying_key().to_bytes())
+}
+
+/// Headers authenticating a command sent to `target`.
+pub fn command_headers(
+    key: &SigningKey,
+    method: &str,
+    path: &str,
+    target: &str,
+    body: &[u8],
+) -> Vec<(&'static str, String)> {
+    let nonce = crate::auth::new_token();
+    let expires = (chrono::Utc::now().timestamp() + COMMAND_TTL_SECS).to_string();
+
+    let mut message = format!("{method}\n{path}\n{target}\n{nonce}\n{expires}\n").into_bytes();
+    message.extend_from_slice(body);
+    let signature = hex::encode(key.sign(&message).to_bytes());
+
+    vec![
+        (TARGET_HEADER, target.to_string()),
+        (NONCE_HEADER, nonce),
+        (EXPIRES_HEADER, expires),
+        (SIGNATURE_HEADER, signature),
+    ]
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn private_files_are_owner_only() {
+        let path = std::env::temp_dir().join(format!("nishack-key-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        fs::write(path, "old").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(check_private(path).is_err());
+
+        write_private(path, b"secret").unwrap();
+        assert_eq!(fs::metadata(path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(path).unwrap(), "secret");
+        assert!(check_private(path).is_ok());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,41 +1,179 @@
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
+use ed25519_dalek::SigningKey;
 use redis::aio::ConnectionManager;
-use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock as StdRwLock};
+use tokio::sync::{mpsc, oneshot, RwLock};
 
 use crate::config::Config;
+use crate::lessons;
+use crate::models::{BanPolicy, LessonSession, Role, RosterStudent};
+use crate::policy::PolicyState;
+use crate::rate_limit::RateLimiter;
 
 pub type WsTx = mpsc::UnboundedSender<axum::extract::ws::Message>;
 pub type WsClients = DashMap<String, WsTx>;
 
+/// Waiting for an agent to answer a command: (hostname, reply)
+pub type PendingReply = (String, oneshot::Sender<serde_json::Value>);
+
 /// Per-teacher sender for the screen-relay WebSocket connections
 pub type ScreenTeacherTx = mpsc::UnboundedSender<Vec<u8>>;
 
+/// A teacher dashboard connected to /ws
+pub struct TeacherSession {
+    pub username: String,
+    /// Observers only get presence; student events need the teacher role
+    pub role: Role,
+    pub tx: WsTx,
+    pub connected_at: DateTime<Utc>,
+    /// Student the teacher has open, as last reported by the dashboard
+    pub viewing: Option<String>,
+}
+
+/// Last command sent to a student, for spotting teachers working against each other
+#[derive(Debug, Clone)]
+pub struct RecentCommand {
+    pub value: String,
+    pub by: String,
+    pub at: DateTime<Utc>,
+}
+
+/// A teacher dashboard watching screens, optionally limited to one classroom
+pub struct ScreenViewer {
+    pub tx: ScreenTeacherTx,
+    pub classroom: Option<String>,
+}
+
 pub struct AppState {
+    /// Settings as loaded at startup; the ban lists live in `policy`
     pub config: Config,
+    /// Global ban lists and the active timetable profile
+    pub policy: StdRwLock<PolicyState>,
     pub redis: ConnectionManager,
+    /// Key used to sign commands sent to student agents
+    pub signing_key: SigningKey,
     pub start_time: DateTime<Utc>,
-    /// Chat / general WS clients (keyed by client id)
+    /// Agent WS connections (keyed by hostname)
     pub ws_clients: WsClients,
-    /// Screen-streaming students currently connected (hostname -> WsTx)
-    pub screen_students: DashMap<String, WsTx>,
+    /// Agent command channels on /ws/agent (keyed by hostname)
+    pub agent_channels: WsClients,
+    /// Commands sent over a channel and not yet answered (keyed by command id)
+    pub pending_replies: DashMap<String, PendingReply>,
+    /// Teacher WS connections (keyed by session id)
+    pub teachers: DashMap<String, TeacherSession>,
+    /// Last command per (hostname, kind)
+    pub recent_commands: DashMap<(String, String), RecentCommand>,
+    /// Agent screen streams on /ws/screen (keyed by hostname)
+    pub screen_streams: WsClients,
     /// Teacher dashboard connections waiting for screen frames
-    pub screen_teachers: Arc<RwLock<Vec<ScreenTeacherTx>>>,
+    pub screen_teachers: Arc<RwLock<Vec<ScreenViewer>>>,
     /// Latest JPEG frame per student (hostname -> bytes) — for instant display
     pub screen_latest: DashMap<String, Vec<u8>>,
+    /// Token buckets for /api/agent/* ingestion
+    pub agent_limiter: RateLimiter,
+    /// Classroom assignment cache (hostname -> classroom id)
+    pub student_rooms: DashMap<String, String>,
+    /// Running lessons by scope ("all" or a classroom id), mirrored from Redis
+    pub active_lessons: DashMap<String, LessonSession>,
+    /// Exam-mode findings per student as of their last app report
+    /// (rule, detail), so each one is only raised once
+    pub exam_flags: DashMap<String, HashSet<(String, String)>>,
+    /// Roster by student id, mirrored from Redis
+    pub roster: DashMap<String, RosterStudent>,
 }
 
 impl AppState {
-    pub fn new(config: Config, redis: ConnectionManager) -> Self {
+    /// Whether `hostname` belongs to `classroom` (`None` matches everyone)
+    pub fn in_classroom(&self, hostname: &str, classroom: Option<&str>) -> bool {
+        match classroom {
+            None => true,
+            Some(room) => self.student_rooms.get(hostname).is_some_and(|r| r.as_str() == room),
+        }
+    }
+
+    /// The lesson `hostname` is currently in: its classroom's, else the global one.
+    pub fn lesson_for(&self, hostname: &str) -> Option<LessonSession> {
+        self.student_rooms
+            .get(hostname)
+            .and_then(|room| self.active_lessons.get(room.as_str()).map(|l| l.clone()))
+            .or_else(|| self.active_lessons.get(lessons::ALL_SCOPE).map(|l| l.clone()))
+    }
+
+    /// The roster student at `hostname`: whoever owns the `username` login,
+    /// else the student seated there.
+    pub fn student_for(&self, hostname: &str, username: &str) -> Option<RosterStudent> {
+        let by_login = (!username.is_empty())
+            .then(|| {
+                self.roster.iter().find(|s| {
+                    s.username.as_deref().is_some_and(|u| u.eq_ignore_ascii_case(username))
+                })
+            })
+            .flatten();
+        by_login
+            .or_else(|| self.roster.iter().find(|s| s.hostname.as_deref() == Some(hostname)))
+            .map(|s| s.clone())
+    }
+
+    /// Push an event to every dashboard signed in as teacher or admin.
+    pub fn notify_teachers(&self, msg: &serde_json::Value) {
+        let text = msg.to_string();
+        for t in self.teachers.iter().filter(|t| t.role >= Role::Teacher) {
+            let _ = t.tx.send(axum::extract::ws::Message::Text(text.clone()));
+        }
+    }
+
+    /// Push an event to every dashboard, observers included.
+    pub fn notify_dashboards(&self, msg: &serde_json::Value) {
+        let text = msg.to_string();
+        for t in self.teachers.iter() {
+            let _ = t.tx.send(axum::extract::ws::Message::Text(text.clone()));
+        }
+    }
+
+    /// Close every connection `hostname`'s agent has open: its command
+    /// channel, chat socket and screen stream.
+    pub fn disconnect_agent(&self, hostname: &str) {
+        for conns in [&self.agent_channels, &self.ws_clients, &self.screen_streams] {
+            if let Some((_, tx)) = conns.remove(hostname) {
+                let _ = tx.send(axum::extract::ws::Message::Close(None));
+            }
+        }
+        // Commands waiting on the channel fail now rather than at the timeout
+        self.pending_replies.retain(|_, (target, _)| target != hostname);
+    }
+
+    /// The global ban policy agents should enforce right now
+    pub fn effective_policy(&self) -> BanPolicy {
+        self.policy.read().unwrap().effective(&self.config)
+    }
+
+    /// The config.toml lists as last saved (ignores the timetable)
+    pub fn base_policy(&self) -> BanPolicy {
+        self.policy.read().unwrap().base.clone()
+    }
+
+    pub fn new(config: Config, redis: ConnectionManager, signing_key: SigningKey) -> Self {
         Self {
-            config,
+            policy: StdRwLock::new(PolicyState::new(&config)),
             redis,
+            signing_key,
             start_time: Utc::now(),
             ws_clients: DashMap::new(),
-            screen_students: DashMap::new(),
+            agent_channels: DashMap::new(),
+            pending_replies: DashMap::new(),
+            teachers: DashMap::new(),
+            recent_commands: DashMap::new(),
+            screen_streams: DashMap::new(),
             screen_teachers: Arc::new(RwLock::new(Vec::new())),
             screen_latest: DashMap::new(),
+            agent_limiter: RateLimiter::new(config.agent_rate_per_sec, config.agent_rate_burst),
+            student_rooms: DashMap::new(),
+            active_lessons: DashMap::new(),
+            exam_flags: DashMap::new(),
+            roster: DashMap::new(),
+            config,
         }
     }
 }
@@ -0,0 +1,169 @@
+// ─────────────────────────────────────────────────────────────────
+//  targets.rs — Which students a command is aimed at
+//
+//  Command bodies flatten a `Target` in next to their own fields:
+//    { "mode": "soft", "group": "row-a" }
+//    { "url": "...", "hostnames": ["LAB2-PC01", "LAB2-PC02"] }
+//  Selectors are combined (union). With none (a broadcast) every
+//  agent with a live heartbeat or command channel is targeted, so
+//  retired or switched-off machines still in the registry are left
+//  out; a broadcast unlock also reaches every machine with a lock
+//  recorded, online or not. A host registered under several
+//  addresses is sent to once, at the address it last reported.
+// ─────────────────────────────────────────────────────────────────
+
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::agent_client::AgentAddr;
+use crate::models::Heartbeat;
+use crate::redis_store;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Target {
+    /// Named group (see /api/groups)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Explicit hostname list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostnames: Option<Vec<String>>,
+    /// Everyone assigned to a classroom
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classroom: Option<String>,
+}
+
+impl Target {
+    pub fn group(name: &str) -> Self {
+        Self { group: Some(name.to_string()), ..Self::default() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.group.is_none() && self.hostnames.is_none() && self.classroom.is_none()
+    }
+}
+
+/// Registered agents a target resolved to, plus named hosts that are not registered
+pub struct Resolved {
+    pub agents: Vec<AgentAddr>,
+    pub missing: Vec<String>,
+}
+
+impl Resolved {
+    pub fn hostnames(&self) -> Vec<String> {
+        self.agents.iter().map(|a| a.hostname.clone()).collect()
+    }
+}
+
+/// The agent registry with one address per hostname, plus the live
+/// heartbeats of those hosts.
+async fn registry(state: &AppState) -> Result<(Vec<AgentAddr>, HashMap<String, Heartbeat>), StatusCode> {
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    let mut entries: Vec<AgentAddr> = redis_store::get_all_agents(&mut conn, prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .iter()
+        .filter_map(|e| AgentAddr::parse(e))
+        .collect();
+    entries.sort_by(|a, b| (&a.hostname, &a.ip, &a.port).cmp(&(&b.hostname, &b.ip, &b.port)));
+
+    let mut hostnames: Vec<String> = entries.iter().map(|a| a.hostname.clone()).collect();
+    hostnames.dedup();
+    let heartbeats = redis_store::get_heartbeats(&mut conn, prefix, &hostnames)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Stale entries are left behind when a machine changes address
+    let mut agents: Vec<AgentAddr> = Vec::with_capacity(hostnames.len());
+    for entry in entries {
+        let current = heartbeats
+            .get(&entry.hostname)
+            .is_some_and(|hb| hb.ip == entry.ip && hb.port.to_string() == entry.port);
+        match agents.last_mut() {
+            Some(last) if last.hostname == entry.hostname => {
+                if current {
+                    *last = entry;
+                }
+            }
+            _ => agents.push(entry),
+        }
+    }
+    Ok((agents, heartbeats))
+}
+
+/// Resolve a target against the agent registry. Unknown groups are a 404.
+pub async fn resolve(state: &AppState, target: &Target) -> Result<Resolved, StatusCode> {
+    let (registry, heartbeats) = registry(state).await?;
+
+    if target.is_empty() {
+        let agents = registry
+            .into_iter()
+            .filter(|a| heartbeats.contains_key(&a.hostname) || state.agent_channels.contains_key(&a.hostname))
+            .collect();
+        return Ok(Resolved { agents, missing: Vec::new() });
+    }
+
+    let mut conn = state.redis.clone();
+    let prefix = &state.config.key_prefix;
+
+    // Hosts asked for by name; these are reported when not registered
+    let mut named: BTreeSet<String> = BTreeSet::new();
+    if let Some(hostnames) = &target.hostnames {
+        named.extend(hostnames.iter().map(|h| h.trim().to_string()).filter(|h| !h.is_empty()));
+    }
+    if let Some(group) = &target.group {
+        redis_store::get_group(&mut conn, prefix, group)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let members = redis_store::get_group_members(&mut conn, prefix, group)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        named.extend(members);
+    }
+
+    let room = target.classroom.as_deref();
+    let agents: Vec<AgentAddr> = registry
+        .into_iter()
+        .filter(|a| named.contains(&a.hostname) || (room.is_some() && state.in_classroom(&a.hostname, room)))
+        .collect();
+    let missing = named
+        .into_iter()
+        .filter(|h| !agents.iter().any(|a| &a.hostname == h))
+        .collect();
+
+    Ok(Resolved { agents, missing })
+}
+
+/// Resolve the target of an unlock. A broadcast also takes in every host
+/// with a lock recorded, so offline ones get the unlock queued rather than
+/// being locked again when they come back.
+pub async fn resolve_unlock(state: &AppState, target: &Target) -> Result<Resolved, StatusCode> {
+    let mut resolved = resolve(state, target).await?;
+    if !target.is_empty() {
+        return Ok(resolved);
+    }
+
+    let mut conn = state.redis.clone();
+    let locked: BTreeSet<String> = redis_store::get_locked_hosts(&mut conn, &state.config.key_prefix)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter(|h| !resolved.agents.iter().any(|a| &a.hostname == h))
+        .collect();
+    if locked.is_empty() {
+        return Ok(resolved);
+    }
+    let (registry, _) = registry(state).await?;
+    resolved
+        .agents
+        .extend(registry.into_iter().filter(|a| locked.contains(&a.hostname)));
+    resolved.missing = locked
+        .into_iter()
+        .filter(|h| !resolved.agents.iter().any(|a| &a.hostname == h))
+        .collect();
+    Ok(resolved)
+}
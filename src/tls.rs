@@ -0,0 +1,69 @@
+// ─────────────────────────────────────────────────────────────────
+//  tls.rs — HTTPS/WSS listener setup
+//
+//  Loads the certificate from `tls_cert_path` / `tls_key_path`, or
+//  generates a self-signed one for this machine on first start.
+//  The SHA-256 fingerprint of the certificate is published to Redis
+//  so agents can pin it.
+// ─────────────────────────────────────────────────────────────────
+
+use axum_server::tls_rustls::RustlsConfig;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::server_key;
+
+pub struct TlsSetup {
+    pub rustls: RustlsConfig,
+    /// Lowercase hex SHA-256 of the leaf certificate (DER)
+    pub fingerprint: String,
+}
+
+pub async fn load_or_generate(cfg: &Config) -> Result<TlsSetup, String> {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    if !Path::new(&cfg.tls_cert_path).exists() || !Path::new(&cfg.tls_key_path).exists() {
+        generate_self_signed(&cfg.tls_cert_path, &cfg.tls_key_path)?;
+    }
+
+    let cert_pem = std::fs::read(&cfg.tls_cert_path)
+        .map_err(|e| format!("Cannot read {}: {e}", cfg.tls_cert_path))?;
+    let leaf = rustls_pemfile::certs(&mut cert_pem.as_slice())
+        .next()
+        .ok_or_else(|| format!("No certificate found in {}", cfg.tls_cert_path))?
+        .map_err(|e| format!("Invalid certificate in {}: {e}", cfg.tls_cert_path))?;
+    let fingerprint = hex::encode(Sha256::digest(leaf.as_ref()));
+
+    let rustls = RustlsConfig::from_pem_file(&cfg.tls_cert_path, &cfg.tls_key_path)
+        .await
+        .map_err(|e| format!("Cannot load TLS certificate: {e}"))?;
+
+    Ok(TlsSetup { rustls, fingerprint })
+}
+
+/// Self-signed certificate valid for localhost, this machine's hostname and LAN IP.
+fn generate_self_signed(cert_path: &str, key_path: &str) -> Result<(), String> {
+    let mut names = vec!["localhost".to_string()];
+    if let Some(host) = sysinfo::System::host_name() {
+        names.push(host);
+    }
+    if let Ok(ip) = local_ip_address::local_ip() {
+        names.push(ip.to_string());
+    }
+
+    let cert = rcgen::generate_simple_self_signed(names.clone())
+        .map_err(|e| format!("Cannot generate certificate: {e}"))?;
+
+    for path in [cert_path, key_path] {
+        if let Some(dir) = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {e}", dir.display()))?;
+        }
+    }
+    std::fs::write(cert_path, cert.cert.pem()).map_err(|e| format!("Cannot write {cert_path}: {e}"))?;
+    server_key::write_private(key_path, cert.key_pair.serialize_pem().as_bytes())
+        .map_err(|e| format!("Cannot write {key_path}: {e}"))?;
+
+    tracing::info!("🔐 Generated self-signed certificate for {}", names.join(", "));
+    Ok(())
+}